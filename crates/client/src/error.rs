@@ -0,0 +1,101 @@
+use reqwest::header::ETAG;
+use std::fmt;
+
+use restaurant_api_models::response::GenericResponse;
+
+#[derive(Debug)]
+pub enum Error {
+    // The request could not be sent or its response could not be read
+    Request(reqwest::Error),
+    // The server answered with an error status. `msg` comes from the GenericResponse envelope,
+    // or is the raw body for the few routes that answer errors with something else.
+    Api {
+        status: u16,
+        msg: String,
+        // Current ETag of the row on a 412, so the caller can decide whether to retry
+        etag: Option<String>,
+    },
+    // A body that does not match the models
+    Json(serde_json::Error),
+    // Boxed, the tungstenite error would make every Result in the crate large
+    #[cfg(feature = "ws")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+}
+
+impl Error {
+    // Status of an error response, None when the server never answered
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Api { status, .. } => Some(*status),
+            Error::Request(err) => err.status().map(|status| status.as_u16()),
+            _ => None,
+        }
+    }
+
+    pub(crate) async fn from_response(response: reqwest::Response) -> Error {
+        let status = response.status();
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| etag.to_string());
+        let body = match response.bytes().await {
+            Ok(body) => body,
+            Err(err) => return Error::Request(err),
+        };
+
+        let msg = match serde_json::from_slice::<GenericResponse>(&body) {
+            Ok(generic) => generic.msg,
+            Err(_) if body.is_empty() => status.canonical_reason().unwrap_or_default().to_string(),
+            Err(_) => String::from_utf8_lossy(&body).to_string(),
+        };
+        Error::Api {
+            status: status.as_u16(),
+            msg,
+            etag,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Request(err) => write!(f, "Request failed: {}", err),
+            Error::Api { status, msg, .. } => write!(f, "Server returned {}: {}", status, msg),
+            Error::Json(err) => write!(f, "Unexpected body: {}", err),
+            #[cfg(feature = "ws")]
+            Error::WebSocket(err) => write!(f, "Websocket failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Request(err) => Some(err),
+            Error::Api { .. } => None,
+            Error::Json(err) => Some(err),
+            #[cfg(feature = "ws")]
+            Error::WebSocket(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Request(err)
+    }
+}
+
+#[cfg(feature = "ws")]
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(err))
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
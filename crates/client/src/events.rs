@@ -0,0 +1,115 @@
+use restaurant_api_models::response::{EventMessage, StreamMessage};
+
+use crate::Error;
+
+// What comes down an event stream: events carry an id, control messages (subscription acks,
+// lagged notices, errors) do not
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    Event(EventMessage),
+    Control(StreamMessage),
+}
+
+impl StreamEvent {
+    fn parse(data: &str) -> Result<StreamEvent, Error> {
+        match serde_json::from_str::<EventMessage>(data) {
+            Ok(message) => Ok(StreamEvent::Event(message)),
+            Err(_) => Ok(StreamEvent::Control(serde_json::from_str(data)?)),
+        }
+    }
+}
+
+// Server-Sent Events from /events/sse. Reconnect with the id of the last event seen to have the
+// server replay what was missed.
+pub struct EventStream {
+    response: reqwest::Response,
+    // Bytes received but not yet split into lines, a chunk can end mid character
+    buffer: Vec<u8>,
+}
+
+impl EventStream {
+    pub(crate) fn new(response: reqwest::Response) -> EventStream {
+        EventStream {
+            response,
+            buffer: Vec::new(),
+        }
+    }
+
+    // Next event or control message, skipping keep-alive comments. None once the server closes
+    // the stream.
+    pub async fn next_event(&mut self) -> Result<Option<StreamEvent>, Error> {
+        let mut data = String::new();
+        loop {
+            let Some(line) = self.next_line().await? else {
+                return Ok(None);
+            };
+
+            if let Some(value) = line.strip_prefix("data:") {
+                data.push_str(value.trim());
+            } else if line.is_empty() && !data.is_empty() {
+                return StreamEvent::parse(&data).map(Some);
+            }
+            // `id:` lines repeat the id in the event body, comments start with `:`
+        }
+    }
+
+    async fn next_line(&mut self) -> Result<Option<String>, Error> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                return Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()));
+            }
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ws")]
+type Socket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+// The /events/ws socket. The first message is the subscription ack.
+#[cfg(feature = "ws")]
+pub struct EventSocket {
+    socket: Socket,
+}
+
+#[cfg(feature = "ws")]
+impl EventSocket {
+    pub(crate) fn new(socket: Socket) -> EventSocket {
+        EventSocket { socket }
+    }
+
+    // Next event or control message. None once the server closes the socket.
+    pub async fn next_event(&mut self) -> Result<Option<StreamEvent>, Error> {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::Message;
+
+        while let Some(message) = self.socket.next().await {
+            match message? {
+                Message::Text(text) => return StreamEvent::parse(&text).map(Some),
+                Message::Close(_) => return Ok(None),
+                _ => continue,
+            }
+        }
+        Ok(None)
+    }
+
+    // Switches the socket to other tables, None for the whole kitchen. The server acks with a
+    // new `subscribed` message.
+    pub async fn subscribe(&mut self, tables: Option<Vec<u32>>) -> Result<(), Error> {
+        use futures_util::SinkExt;
+        use restaurant_api_models::request::SubscribeRequest;
+        use tokio_tungstenite::tungstenite::Message;
+
+        let request = serde_json::to_string(&SubscribeRequest { tables })?;
+        self.socket
+            .send(Message::Text(request))
+            .await
+            .map_err(Error::from)
+    }
+}
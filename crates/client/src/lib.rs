@@ -0,0 +1,658 @@
+// Typed async client for the restaurant API, one method per route.
+// Requests and responses use the same models as the server.
+use rand::Rng;
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_MATCH, RETRY_AFTER};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::Duration;
+
+mod error;
+mod events;
+pub use error::Error;
+#[cfg(feature = "ws")]
+pub use events::EventSocket;
+pub use events::{EventStream, StreamEvent};
+pub use reqwest::Method;
+pub use restaurant_api_models as models;
+
+use models::database::{Items, Table};
+use models::request::{
+    AddItemsRequest, BulkFormat, CreateApiKeyRequest, CreateStaffRequest, CreateWebhookRequest,
+    GetItemRequest, ImportMode, LoginRequest, LogoutRequest, RefreshRequest, ReportFormat,
+    ReportGroup, ReportQuery, SearchItemsRequest, TableItem,
+};
+use models::response::{
+    ApiKeysResponse, CreateApiKeyResponse, CreateWebhookResponse, DeadLettersResponse,
+    GenericResponse, GetSeatsResponse, ImportReport, ItemsResponse, ReadinessResponse,
+    ReportResponse, StaffResponse, TokenResponse, WebhooksResponse,
+};
+
+const API_KEY_HEADER: &str = "x-api-key";
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+// Statuses worth another attempt: rate limited, or a proxy/server that is briefly unavailable
+const RETRY_STATUSES: [u16; 4] = [429, 502, 503, 504];
+
+// A response body along with the ETag it was served with, for conditional deletes
+#[derive(Debug, Clone)]
+pub struct Versioned<T> {
+    pub body: T,
+    pub etag: Option<String>,
+}
+
+// Retries back off exponentially from `initial_backoff` up to `max_backoff`.
+// A Retry-After header on a 429 is honoured, capped at `max_backoff`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = retry_after.unwrap_or_else(|| {
+            self.initial_backoff
+                .saturating_mul(2u32.saturating_pow(attempt))
+        });
+        backoff.min(self.max_backoff)
+    }
+}
+
+#[derive(Clone)]
+enum Credentials {
+    ApiKey(String),
+    Bearer(String),
+}
+
+// Whether a call can be sent again after a failed attempt
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Retry {
+    // Reads, including the ones sent as POST
+    Safe,
+    // Writes on protected routes, made safe by sending the same Idempotency-Key on every attempt
+    WithKey,
+    // Writes the server can not deduplicate, e.g. refresh tokens are single use
+    Never,
+}
+
+struct Call {
+    method: Method,
+    path: String,
+    headers: Vec<(&'static str, String)>,
+    body: Option<(Vec<u8>, &'static str)>,
+    retry: Retry,
+}
+
+impl Call {
+    fn new(method: Method, path: impl Into<String>, retry: Retry) -> Call {
+        Call {
+            method,
+            path: path.into(),
+            headers: Vec::new(),
+            body: None,
+            retry,
+        }
+    }
+
+    fn json<T: Serialize>(mut self, body: &T) -> Result<Call, Error> {
+        self.body = Some((serde_json::to_vec(body)?, "application/json"));
+        Ok(self)
+    }
+
+    fn text(mut self, body: &str, content_type: &'static str) -> Call {
+        self.body = Some((body.as_bytes().to_vec(), content_type));
+        self
+    }
+
+    // Values that are not valid in a header fail when the call is sent
+    fn header(mut self, name: &'static str, value: Option<&str>) -> Call {
+        if let Some(value) = value {
+            self.headers.push((name, value.to_string()));
+        }
+        self
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    credentials: Option<Credentials>,
+    retry: RetryPolicy,
+    idempotency_key: Option<String>,
+}
+
+impl Client {
+    // `base_url` is the scheme, host and port the server listens on, e.g. http://localhost:8080
+    pub fn new(base_url: impl Into<String>) -> Client {
+        Client::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    // For callers that need their own timeouts, proxies or TLS settings
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Client {
+        Client {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            credentials: None,
+            retry: RetryPolicy::default(),
+            idempotency_key: None,
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Client {
+        self.credentials = Some(Credentials::ApiKey(api_key.into()));
+        self
+    }
+
+    pub fn with_bearer_token(mut self, access_token: impl Into<String>) -> Client {
+        self.credentials = Some(Credentials::Bearer(access_token.into()));
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Client {
+        self.retry = retry;
+        self
+    }
+
+    // Writes normally get a fresh Idempotency-Key per call. A fixed key lets a caller retry a
+    // write across restarts, or find out whether an earlier attempt went through.
+    pub fn with_idempotency_key(&self, key: impl Into<String>) -> Client {
+        Client {
+            idempotency_key: Some(key.into()),
+            ..self.clone()
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // Escape hatch for anything the typed methods do not cover, e.g. reading response headers.
+    // Credentials are attached, retries are not.
+    pub fn request(&self, method: Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path));
+        match &self.credentials {
+            Some(Credentials::ApiKey(api_key)) => request.header(API_KEY_HEADER, api_key),
+            Some(Credentials::Bearer(access_token)) => request.bearer_auth(access_token),
+            None => request,
+        }
+    }
+
+    // Health
+
+    pub async fn liveness(&self) -> Result<GenericResponse, Error> {
+        self.json(Call::new(Method::GET, "/health/live", Retry::Safe))
+            .await
+    }
+
+    // A 503 comes back as Error::Api with the ReadinessResponse as its msg
+    pub async fn readiness(&self) -> Result<ReadinessResponse, Error> {
+        self.json(Call::new(Method::GET, "/health/ready", Retry::Safe))
+            .await
+    }
+
+    // Prometheus text format
+    pub async fn get_metrics(&self) -> Result<String, Error> {
+        let response = self
+            .send(Call::new(Method::GET, "/metrics", Retry::Safe))
+            .await?;
+        Ok(response.text().await?)
+    }
+
+    pub async fn get_openapi(&self) -> Result<serde_json::Value, Error> {
+        self.json(Call::new(Method::GET, "/openapi.json", Retry::Safe))
+            .await
+    }
+
+    // Tables
+
+    pub async fn get_seats(&self, table_id: u32) -> Result<Versioned<GetSeatsResponse>, Error> {
+        self.versioned(Call::new(
+            Method::GET,
+            format!("/table/{}", table_id),
+            Retry::Safe,
+        ))
+        .await
+    }
+
+    pub async fn add_table(&self, table: &Table) -> Result<GenericResponse, Error> {
+        self.json(Call::new(Method::PUT, "/table/add", Retry::WithKey).json(table)?)
+            .await
+    }
+
+    // Deletes only go ahead while the table still has one of the ETags in `if_match`
+    pub async fn delete_table_by_id(
+        &self,
+        table_id: u32,
+        if_match: Option<&str>,
+    ) -> Result<GenericResponse, Error> {
+        self.json(
+            Call::new(
+                Method::DELETE,
+                format!("/table/delete/{}", table_id),
+                Retry::WithKey,
+            )
+            .header(IF_MATCH.as_str(), if_match),
+        )
+        .await
+    }
+
+    pub async fn undo_table(&self, table_id: u32) -> Result<GenericResponse, Error> {
+        self.json(Call::new(
+            Method::PUT,
+            format!("/table/undo/{}", table_id),
+            Retry::WithKey,
+        ))
+        .await
+    }
+
+    // Items
+
+    pub async fn get_items(&self, request: &GetItemRequest) -> Result<ItemsResponse, Error> {
+        self.json(Call::new(Method::POST, "/items", Retry::Safe).json(request)?)
+            .await
+    }
+
+    pub async fn search_items(&self, request: &SearchItemsRequest) -> Result<ItemsResponse, Error> {
+        self.json(Call::new(Method::POST, "/items/search", Retry::Safe).json(request)?)
+            .await
+    }
+
+    pub async fn get_item(&self, item_id: u32) -> Result<Versioned<Items>, Error> {
+        self.versioned(Call::new(
+            Method::GET,
+            format!("/items/{}", item_id),
+            Retry::Safe,
+        ))
+        .await
+    }
+
+    pub async fn add_items(&self, request: &AddItemsRequest) -> Result<GenericResponse, Error> {
+        self.json(Call::new(Method::PUT, "/items/add", Retry::WithKey).json(request)?)
+            .await
+    }
+
+    pub async fn delete_item(&self, item: &TableItem) -> Result<GenericResponse, Error> {
+        self.json(Call::new(Method::DELETE, "/items/delete", Retry::WithKey).json(item)?)
+            .await
+    }
+
+    pub async fn delete_item_by_id(
+        &self,
+        item_id: u32,
+        if_match: Option<&str>,
+    ) -> Result<GenericResponse, Error> {
+        self.json(
+            Call::new(
+                Method::DELETE,
+                format!("/items/delete/{}", item_id),
+                Retry::WithKey,
+            )
+            .header(IF_MATCH.as_str(), if_match),
+        )
+        .await
+    }
+
+    // Auth
+
+    pub async fn login(&self, request: &LoginRequest) -> Result<TokenResponse, Error> {
+        self.json(Call::new(Method::POST, "/auth/login", Retry::Safe).json(request)?)
+            .await
+    }
+
+    // Refresh tokens are single use, so a refresh is never retried
+    pub async fn refresh(&self, request: &RefreshRequest) -> Result<TokenResponse, Error> {
+        self.json(Call::new(Method::POST, "/auth/refresh", Retry::Never).json(request)?)
+            .await
+    }
+
+    pub async fn logout(&self, request: &LogoutRequest) -> Result<GenericResponse, Error> {
+        self.json(Call::new(Method::POST, "/auth/logout", Retry::WithKey).json(request)?)
+            .await
+    }
+
+    // Admin
+
+    pub async fn get_api_keys(&self) -> Result<ApiKeysResponse, Error> {
+        self.json(Call::new(Method::GET, "/admin/keys", Retry::Safe))
+            .await
+    }
+
+    pub async fn create_api_key(
+        &self,
+        request: &CreateApiKeyRequest,
+    ) -> Result<CreateApiKeyResponse, Error> {
+        self.json(Call::new(Method::PUT, "/admin/keys/add", Retry::WithKey).json(request)?)
+            .await
+    }
+
+    pub async fn revoke_api_key(&self, key_id: u32) -> Result<GenericResponse, Error> {
+        self.json(Call::new(
+            Method::DELETE,
+            format!("/admin/keys/delete/{}", key_id),
+            Retry::WithKey,
+        ))
+        .await
+    }
+
+    pub async fn get_staff(&self) -> Result<StaffResponse, Error> {
+        self.json(Call::new(Method::GET, "/admin/staff", Retry::Safe))
+            .await
+    }
+
+    pub async fn create_staff(
+        &self,
+        request: &CreateStaffRequest,
+    ) -> Result<GenericResponse, Error> {
+        self.json(Call::new(Method::PUT, "/admin/staff/add", Retry::WithKey).json(request)?)
+            .await
+    }
+
+    pub async fn delete_staff(&self, staff_id: u32) -> Result<GenericResponse, Error> {
+        self.json(Call::new(
+            Method::DELETE,
+            format!("/admin/staff/delete/{}", staff_id),
+            Retry::WithKey,
+        ))
+        .await
+    }
+
+    pub async fn get_webhooks(&self) -> Result<WebhooksResponse, Error> {
+        self.json(Call::new(Method::GET, "/admin/webhooks", Retry::Safe))
+            .await
+    }
+
+    pub async fn create_webhook(
+        &self,
+        request: &CreateWebhookRequest,
+    ) -> Result<CreateWebhookResponse, Error> {
+        self.json(Call::new(Method::PUT, "/admin/webhooks/add", Retry::WithKey).json(request)?)
+            .await
+    }
+
+    pub async fn delete_webhook(&self, webhook_id: u32) -> Result<GenericResponse, Error> {
+        self.json(Call::new(
+            Method::DELETE,
+            format!("/admin/webhooks/delete/{}", webhook_id),
+            Retry::WithKey,
+        ))
+        .await
+    }
+
+    pub async fn get_dead_letters(&self) -> Result<DeadLettersResponse, Error> {
+        self.json(Call::new(Method::GET, "/admin/webhooks/dead", Retry::Safe))
+            .await
+    }
+
+    pub async fn retry_dead_letter(&self, delivery_id: u64) -> Result<GenericResponse, Error> {
+        self.json(Call::new(
+            Method::PUT,
+            format!("/admin/webhooks/dead/retry/{}", delivery_id),
+            Retry::WithKey,
+        ))
+        .await
+    }
+
+    // Bulk
+
+    // The whole export in one string, each row on its own line
+    pub async fn export_tables(&self, format: BulkFormat) -> Result<String, Error> {
+        self.export("tables", format).await
+    }
+
+    pub async fn export_items(&self, format: BulkFormat) -> Result<String, Error> {
+        self.export("items", format).await
+    }
+
+    // A 422 comes back as Error::Api with the ImportReport as its msg
+    pub async fn import_tables(
+        &self,
+        body: &str,
+        format: BulkFormat,
+        mode: ImportMode,
+    ) -> Result<ImportReport, Error> {
+        self.import("tables", body, format, mode).await
+    }
+
+    pub async fn import_items(
+        &self,
+        body: &str,
+        format: BulkFormat,
+        mode: ImportMode,
+    ) -> Result<ImportReport, Error> {
+        self.import("items", body, format, mode).await
+    }
+
+    async fn export(&self, resource: &str, format: BulkFormat) -> Result<String, Error> {
+        let response = self
+            .send(Call::new(
+                Method::GET,
+                format!("/admin/export/{}?format={}", resource, format.as_str()),
+                Retry::Safe,
+            ))
+            .await?;
+        Ok(response.text().await?)
+    }
+
+    async fn import(
+        &self,
+        resource: &str,
+        body: &str,
+        format: BulkFormat,
+        mode: ImportMode,
+    ) -> Result<ImportReport, Error> {
+        self.json(
+            Call::new(
+                Method::PUT,
+                format!(
+                    "/admin/import/{}?format={}&mode={}",
+                    resource,
+                    format.as_str(),
+                    mode.as_str()
+                ),
+                Retry::WithKey,
+            )
+            .text(body, format.content_type()),
+        )
+        .await
+    }
+
+    // Reports
+
+    // `query.format` is ignored, use get_report_csv for CSV
+    pub async fn get_report(
+        &self,
+        group_by: ReportGroup,
+        query: &ReportQuery,
+    ) -> Result<ReportResponse, Error> {
+        self.json(Call::new(
+            Method::GET,
+            report_path(group_by, query, ReportFormat::Json),
+            Retry::Safe,
+        ))
+        .await
+    }
+
+    pub async fn get_report_csv(
+        &self,
+        group_by: ReportGroup,
+        query: &ReportQuery,
+    ) -> Result<String, Error> {
+        let response = self
+            .send(Call::new(
+                Method::GET,
+                report_path(group_by, query, ReportFormat::Csv),
+                Retry::Safe,
+            ))
+            .await?;
+        Ok(response.text().await?)
+    }
+
+    // Events
+
+    // `tables` limits the stream to those tables, None is the whole kitchen. Passing the id of
+    // the last event seen replays what was missed since.
+    pub async fn events_sse(
+        &self,
+        tables: Option<&[u32]>,
+        last_event_id: Option<u64>,
+    ) -> Result<EventStream, Error> {
+        let last_event_id = last_event_id.map(|id| id.to_string());
+        let call = Call::new(
+            Method::GET,
+            format!("/events/sse{}", tables_query(tables)),
+            Retry::Safe,
+        )
+        .header(LAST_EVENT_ID_HEADER, last_event_id.as_deref());
+        Ok(EventStream::new(self.send(call).await?))
+    }
+
+    #[cfg(feature = "ws")]
+    pub async fn events_ws(&self, tables: Option<&[u32]>) -> Result<EventSocket, Error> {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+        use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+        let url = format!(
+            "{}/events/ws{}",
+            self.base_url.replacen("http", "ws", 1),
+            tables_query(tables)
+        );
+        let mut request = url.into_client_request()?;
+        let credential = match &self.credentials {
+            Some(Credentials::ApiKey(api_key)) => Some((API_KEY_HEADER, api_key.clone())),
+            Some(Credentials::Bearer(access_token)) => {
+                Some(("authorization", format!("Bearer {}", access_token)))
+            }
+            None => None,
+        };
+        if let Some((name, value)) = credential {
+            let value = HeaderValue::from_str(&value)
+                .map_err(|err| tokio_tungstenite::tungstenite::Error::HttpFormat(err.into()))?;
+            request.headers_mut().insert(name, value);
+        }
+        let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(EventSocket::new(socket))
+    }
+
+    // Plumbing
+
+    async fn json<T: DeserializeOwned>(&self, call: Call) -> Result<T, Error> {
+        let response = self.send(call).await?;
+        Ok(serde_json::from_slice(&response.bytes().await?)?)
+    }
+
+    async fn versioned<T: DeserializeOwned>(&self, call: Call) -> Result<Versioned<T>, Error> {
+        let response = self.send(call).await?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| etag.to_string());
+        let body = serde_json::from_slice(&response.bytes().await?)?;
+        Ok(Versioned { body, etag })
+    }
+
+    // Sends the call, retrying as the policy allows. Any status other than 2xx is an Error::Api.
+    async fn send(&self, call: Call) -> Result<reqwest::Response, Error> {
+        let idempotency_key = match call.retry {
+            Retry::WithKey => Some(
+                self.idempotency_key
+                    .clone()
+                    .unwrap_or_else(new_idempotency_key),
+            ),
+            Retry::Safe | Retry::Never => None,
+        };
+
+        let mut attempt = 0;
+        loop {
+            let mut request = self.request(call.method.clone(), &call.path);
+            for (name, value) in &call.headers {
+                request = request.header(*name, value);
+            }
+            if let Some(idempotency_key) = &idempotency_key {
+                request = request.header(IDEMPOTENCY_KEY_HEADER, idempotency_key);
+            }
+            if let Some((body, content_type)) = &call.body {
+                request = request
+                    .header(CONTENT_TYPE, *content_type)
+                    .body(body.clone());
+            }
+
+            let result = request.send().await;
+            let retryable = match &result {
+                Ok(response) => RETRY_STATUSES.contains(&response.status().as_u16()),
+                Err(err) => err.is_connect() || err.is_timeout(),
+            };
+            if retryable && call.retry != Retry::Never && attempt < self.retry.max_retries {
+                let retry_after = result.as_ref().ok().and_then(retry_after);
+                tokio::time::sleep(self.retry.backoff(attempt, retry_after)).await;
+                attempt += 1;
+                continue;
+            }
+
+            let response = result?;
+            if response.status().is_success() {
+                return Ok(response);
+            }
+            return Err(Error::from_response(response).await);
+        }
+    }
+}
+
+fn tables_query(tables: Option<&[u32]>) -> String {
+    match tables {
+        Some(tables) => format!(
+            "?tables={}",
+            tables
+                .iter()
+                .map(|table_id| table_id.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        None => String::new(),
+    }
+}
+
+fn report_path(group_by: ReportGroup, query: &ReportQuery, format: ReportFormat) -> String {
+    let mut path = format!(
+        "/admin/reports/{}?format={}",
+        group_by.as_str(),
+        format.as_str()
+    );
+    for (name, time) in [("from", query.from), ("to", query.to)] {
+        if let Some(time) = time {
+            path.push_str(&format!("&{}={}", name, time.format("%Y-%m-%dT%H:%M:%SZ")));
+        }
+    }
+    path
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let secs = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    secs.parse::<u64>().ok().map(Duration::from_secs)
+}
+
+fn new_idempotency_key() -> String {
+    let bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
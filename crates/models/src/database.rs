@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Also used as response model for table related routes
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Table {
+    pub id: u32,
+    pub seats: u32,
+    // Managed by the server, bumped whenever the row changes
+    #[serde(skip_deserializing, default = "first_version")]
+    #[cfg_attr(feature = "openapi", schema(read_only))]
+    pub version: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Items {
+    pub id: u32,
+    pub table_id: u32,
+    pub item: String,
+    pub cook_time: u8,
+    pub customer_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub version: u32,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+// Permissions an API key can carry. Stored as a comma separated list in `api_keys.scopes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Read,
+    WriteItems,
+    ManageTables,
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::WriteItems => "write_items",
+            Scope::ManageTables => "manage_tables",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Scope> {
+        match scope.trim() {
+            "read" => Some(Scope::Read),
+            "write_items" => Some(Scope::WriteItems),
+            "manage_tables" => Some(Scope::ManageTables),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+// Key hashes are never selected back out of the database
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiKey {
+    pub id: u32,
+    pub name: String,
+    pub scopes: String,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+// Staff roles, each role maps onto the same scopes used by API keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Cook,
+    Server,
+    Manager,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Cook => "cook",
+            Role::Server => "server",
+            Role::Manager => "manager",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Role> {
+        match role {
+            "cook" => Some(Role::Cook),
+            "server" => Some(Role::Server),
+            "manager" => Some(Role::Manager),
+            _ => None,
+        }
+    }
+
+    // Cooks work the items, servers also seat and clear tables, managers can do everything
+    pub fn scopes(&self) -> Vec<Scope> {
+        match self {
+            Role::Cook => vec![Scope::Read, Scope::WriteItems],
+            Role::Server => vec![Scope::Read, Scope::WriteItems, Scope::ManageTables],
+            Role::Manager => vec![Scope::Admin],
+        }
+    }
+}
+
+// Password hashes are never selected back out of the database outside of login
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Staff {
+    pub id: u32,
+    pub username: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// Secrets are only returned when a subscription is created
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebhookSubscription {
+    pub id: u32,
+    pub url: String,
+    // Comma separated event types, empty for every event
+    pub events: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// A row of the webhook outbox
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebhookDelivery {
+    pub id: u64,
+    pub subscription_id: u32,
+    pub event_type: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: u32,
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
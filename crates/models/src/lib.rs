@@ -0,0 +1,5 @@
+// Request, response and database models shared by the server and the client.
+// The server turns on the sqlx, openapi and axum features, the client only needs serde.
+pub mod database;
+pub mod request;
+pub mod response;
@@ -0,0 +1,283 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::database::{Role, Scope};
+
+#[derive(Deserialize, Debug, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetItemRequest {
+    pub table_id: u32,
+    pub item: Option<String>,
+    pub customer_id: Option<String>,
+    // Exclusive bounds on created_at
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: ItemSort,
+    #[serde(default)]
+    pub direction: SortDirection,
+    // Page size, defaults to the server's max page size
+    pub limit: Option<usize>,
+    // next_cursor of the previous page
+    pub cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ItemSort {
+    #[default]
+    CreatedAt,
+    CookTime,
+    Item,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+// Restaurant wide search, every filter is optional
+#[derive(Deserialize, Debug, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SearchItemsRequest {
+    pub item: Option<String>,
+    #[serde(default)]
+    pub item_match: ItemMatch,
+    // Item names match case-insensitively unless this is set
+    #[serde(default)]
+    pub case_sensitive: bool,
+    pub customer_id: Option<String>,
+    pub table_ids: Option<Vec<u32>>,
+    // Exclusive bounds on created_at
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: ItemSort,
+    #[serde(default)]
+    pub direction: SortDirection,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ItemMatch {
+    #[default]
+    Exact,
+    Prefix,
+}
+
+// Used for adding and deleting items
+#[derive(Deserialize, Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TableItem {
+    pub table_id: u32,
+    pub item: String,
+    pub customer_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AddItemsRequest {
+    pub to_add: Vec<TableItem>,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateStaffRequest {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+// The access token being logged out is taken from the Authorization header
+#[derive(Deserialize, Debug, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LogoutRequest {
+    pub refresh_token: Option<String>,
+}
+
+// Query string for the event streams, a comma separated list of table ids. Omitted means every table.
+#[derive(Deserialize, Debug, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct EventsQuery {
+    pub tables: Option<String>,
+}
+
+// Sent over an open websocket to change its subscription, null tables means every table
+#[derive(Deserialize, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SubscribeRequest {
+    pub tables: Option<Vec<u32>>,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    // Event types to deliver, e.g. "item_added". Omitted or empty means every event
+    pub events: Option<Vec<String>>,
+    // Generated when omitted
+    pub secret: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BulkFormat {
+    // Header row first, columns are matched by name
+    Csv,
+    // One JSON object per line
+    #[default]
+    Ndjson,
+}
+
+impl BulkFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BulkFormat::Csv => "csv",
+            BulkFormat::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn parse(format: &str) -> Option<BulkFormat> {
+        match format.trim() {
+            "csv" => Some(BulkFormat::Csv),
+            "ndjson" => Some(BulkFormat::Ndjson),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BulkFormat::Csv => "text/csv; charset=utf-8",
+            BulkFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    // Nothing is imported unless every row is valid
+    #[default]
+    AllOrNothing,
+    // Valid rows are imported, the others are reported
+    BestEffort,
+}
+
+impl ImportMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportMode::AllOrNothing => "all_or_nothing",
+            ImportMode::BestEffort => "best_effort",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Option<ImportMode> {
+        match mode.trim() {
+            "all_or_nothing" => Some(ImportMode::AllOrNothing),
+            "best_effort" => Some(ImportMode::BestEffort),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: BulkFormat,
+}
+
+#[derive(Deserialize, Debug, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub format: BulkFormat,
+    #[serde(default)]
+    pub mode: ImportMode,
+}
+
+// What a report groups items by
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ReportGroup {
+    Dish,
+    // Hour of day in UTC, 0 to 23
+    Hour,
+    Table,
+    Customer,
+}
+
+impl ReportGroup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportGroup::Dish => "dish",
+            ReportGroup::Hour => "hour",
+            ReportGroup::Table => "table",
+            ReportGroup::Customer => "customer",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv",
+        }
+    }
+}
+
+// Items created from `from` up to but not including `to`. Without `from` the report covers
+// today in UTC, without `to` it covers the day from `from`.
+#[derive(Deserialize, Debug, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct ReportQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub format: ReportFormat,
+}
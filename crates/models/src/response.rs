@@ -0,0 +1,226 @@
+use super::database::{ApiKey, Items, Scope, Staff, Table, WebhookDelivery, WebhookSubscription};
+use super::request::ReportGroup;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GenericResponse {
+    pub msg: String,
+    pub status_code: u16,
+    pub rows: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetSeatsResponse {
+    pub seats: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ItemsResponse {
+    pub items: Vec<Items>,
+    // Pass back as `cursor` to get the next page, null on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateApiKeyResponse {
+    pub id: u64,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    // Plaintext key, only ever returned once at creation
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiKeysResponse {
+    pub keys: Vec<ApiKey>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StaffResponse {
+    pub staff: Vec<Staff>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    // Seconds until the access token expires
+    pub expires_in: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ComponentStatus {
+    pub name: String,
+    // "up" or "down"
+    pub status: String,
+    pub latency_ms: f64,
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReadinessResponse {
+    // "ready" or "unavailable"
+    pub status: String,
+    pub components: Vec<ComponentStatus>,
+}
+
+// The secret is only returned here, deliveries are signed with it
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateWebhookResponse {
+    pub id: u64,
+    pub url: String,
+    pub events: Vec<String>,
+    pub secret: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebhooksResponse {
+    pub webhooks: Vec<WebhookSubscription>,
+}
+
+// Deliveries that ran out of attempts
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeadLettersResponse {
+    pub deliveries: Vec<WebhookDelivery>,
+}
+
+// Outcome of an import. Nothing was imported when an all_or_nothing import reports errors.
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportReport {
+    pub imported: u64,
+    pub failed: u64,
+    // The first errors in line order, `failed` counts all of them
+    pub errors: Vec<ImportError>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportError {
+    // 1-based, the CSV header is line 1
+    pub line: usize,
+    pub msg: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReportResponse {
+    pub group_by: ReportGroup,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub total_items: u64,
+    pub rows: Vec<ReportRow>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReportRow {
+    // Dish name, hour of day, table id or customer id. Null groups the items without a customer
+    pub key: Option<String>,
+    pub items: u64,
+    // Items have no prices yet, so this is always null
+    pub revenue: Option<f64>,
+    // Rounded to two decimals
+    pub avg_cook_time: f64,
+}
+
+// Changes to tables and items, pushed to event stream subscribers
+// Tables and items can only be added and deleted for now, there is no way to move an item or
+// change its status, so there are no events for those until the API grows such operations
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    ItemAdded { item: Items },
+    ItemDeleted { item: Items },
+    TableAdded { table: Table },
+    TableDeleted { table_id: u32 },
+}
+
+impl DomainEvent {
+    pub const TYPES: [&'static str; 4] =
+        ["item_added", "item_deleted", "table_added", "table_deleted"];
+
+    // Matches the serialized "type" tag
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            DomainEvent::ItemAdded { .. } => "item_added",
+            DomainEvent::ItemDeleted { .. } => "item_deleted",
+            DomainEvent::TableAdded { .. } => "table_added",
+            DomainEvent::TableDeleted { .. } => "table_deleted",
+        }
+    }
+
+    pub fn table_id(&self) -> u32 {
+        match self {
+            DomainEvent::ItemAdded { item } | DomainEvent::ItemDeleted { item } => item.table_id,
+            DomainEvent::TableAdded { table } => table.id,
+            DomainEvent::TableDeleted { table_id } => *table_id,
+        }
+    }
+
+    pub fn items_added(items: &[Items]) -> Vec<DomainEvent> {
+        items
+            .iter()
+            .map(|item| DomainEvent::ItemAdded { item: item.clone() })
+            .collect()
+    }
+
+    pub fn items_deleted(items: &[Items]) -> Vec<DomainEvent> {
+        items
+            .iter()
+            .map(|item| DomainEvent::ItemDeleted { item: item.clone() })
+            .collect()
+    }
+}
+
+// Ids increase by one per event, so a gap means events were missed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EventMessage {
+    pub id: u64,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: DomainEvent,
+}
+
+// Messages about the stream itself rather than the data
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamMessage {
+    // No tables means the whole kitchen
+    Subscribed { tables: Option<Vec<u32>> },
+    // The client fell behind and this many events were dropped, it should refetch
+    Lagged { missed: u64 },
+    Error { msg: String },
+}
+
+// Used for everything that is not get_sets or get_items
+// In hinde sight, not super necessary since axum::Json has .into_response() implemented
+// Still useful for more descriptive error responses
+#[cfg(feature = "axum")]
+impl axum::response::IntoResponse for GenericResponse {
+    fn into_response(self) -> axum::response::Response {
+        axum::http::Response::builder()
+            .status(self.status_code)
+            .body(axum::body::Body::from(
+                serde_json::to_string(&self).unwrap_or_else(|_| "".to_string()),
+            ))
+            .unwrap()
+    }
+}
@@ -0,0 +1,261 @@
+// Load simulator: virtual servers and cooks work a set of tables concurrently through the API,
+// then the run is summarised and the database is checked for anything the load left behind.
+use clap::Parser;
+use dotenv::dotenv;
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use restaurant_api_client::models::database::{Items, Table};
+use restaurant_api_client::models::request::{BulkFormat, SearchItemsRequest};
+use restaurant_api_client::{Client, Error, RetryPolicy};
+
+mod stats;
+mod workload;
+use stats::Stats;
+use workload::{run_cook, run_server, Mix, Workload};
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Runs virtual servers and cooks against the restaurant API and reports how it held up"
+)]
+struct Cli {
+    /// API to load, defaults to http://APP_HOST:APP_PORT
+    #[arg(long, value_name = "URL")]
+    base_url: Option<String>,
+    /// Admin key, the orphan check reads the exports, defaults to ADMIN_API_KEY
+    #[arg(long, value_name = "KEY")]
+    api_key: Option<String>,
+    /// Virtual servers taking orders
+    #[arg(long, value_name = "N", default_value_t = 8)]
+    servers: usize,
+    /// Virtual cooks serving dishes
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    cooks: usize,
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    duration_secs: u64,
+    /// Tables the run creates and removes again, starting at --first-table-id
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    tables: u32,
+    /// Start of the table ids, pick a range real tables don't use
+    #[arg(long, value_name = "ID", default_value_t = 9000)]
+    first_table_id: u32,
+    /// Weights of the calls servers make
+    #[arg(long, value_name = "MIX", default_value = "add=50,get=30,delete=20")]
+    server_mix: Mix,
+    /// Weights of the calls cooks make, cooks don't add items
+    #[arg(long, value_name = "MIX", default_value = "get=40,delete=60")]
+    cook_mix: Mix,
+    /// Longest random pause between two calls of one client
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    think_ms: u64,
+    /// Retry rate limited and failed calls like the client does by default, instead of counting them as errors.
+    /// Every virtual client shares one IP, so the server's per IP limit (100 burst, 50/s by default) caps the whole run
+
+    #[arg(long)]
+    retry: bool,
+    /// Leave the tables and their items in place after the run
+    #[arg(long)]
+    keep_tables: bool,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+    let cli = Cli::parse();
+    if cli.cook_mix.add > 0 {
+        eprintln!("--cook-mix can't include add, cooks don't add items");
+        return ExitCode::FAILURE;
+    }
+    if cli.tables == 0 || cli.servers + cli.cooks == 0 {
+        eprintln!("Need at least one table and one server or cook");
+        return ExitCode::FAILURE;
+    }
+
+    let base_url = cli.base_url.clone().unwrap_or_else(|| {
+        let app_host = std::env::var("APP_HOST").unwrap_or_else(|_| "localhost".to_string());
+        let app_port = std::env::var("APP_PORT").unwrap_or_else(|_| "8080".to_string());
+        format!("http://{}:{}", app_host, app_port)
+    });
+    let Some(api_key) = cli
+        .api_key
+        .clone()
+        .or_else(|| std::env::var("ADMIN_API_KEY").ok())
+    else {
+        eprintln!("No API key, pass --api-key or set ADMIN_API_KEY");
+        return ExitCode::FAILURE;
+    };
+    let retry = if cli.retry {
+        RetryPolicy::default()
+    } else {
+        RetryPolicy::none()
+    };
+    let client = Client::new(&base_url)
+        .with_api_key(api_key)
+        .with_retry(retry);
+
+    let tables: Arc<[u32]> = (cli.first_table_id..cli.first_table_id + cli.tables).collect();
+    if let Err(err) = create_tables(&client, &tables).await {
+        eprintln!("Failed to set up tables: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    println!(
+        "Running {} servers and {} cooks on tables {}..={} for {}s against {}\n",
+        cli.servers,
+        cli.cooks,
+        tables[0],
+        tables[tables.len() - 1],
+        cli.duration_secs,
+        base_url
+    );
+    if !cli.retry {
+        println!(
+            "Calls are not retried, with rate limiting on most of them past the server's per IP limit come back as 429s\n"
+        );
+    }
+    let started = Instant::now();
+    let workload = Workload {
+        client: client.clone(),
+        tables: tables.clone(),
+        deadline: started + Duration::from_secs(cli.duration_secs),
+        think_time: Duration::from_millis(cli.think_ms),
+    };
+    let mut workers = Vec::new();
+    for _ in 0..cli.servers {
+        workers.push(tokio::spawn(run_server(workload.clone(), cli.server_mix)));
+    }
+    for _ in 0..cli.cooks {
+        workers.push(tokio::spawn(run_cook(workload.clone(), cli.cook_mix)));
+    }
+    let mut stats = Stats::default();
+    for worker in workers {
+        stats.merge(worker.await.expect("Virtual client panicked"));
+    }
+    let elapsed = started.elapsed();
+    stats.print_report(elapsed);
+
+    match check_invariants(&client, &tables, &stats, cli.keep_tables).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("\nFailed to check invariants: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Starts from empty tables, whatever an earlier run left behind is removed first
+async fn create_tables(client: &Client, tables: &[u32]) -> Result<(), Error> {
+    for &table_id in tables {
+        client.delete_table_by_id(table_id, None).await?;
+        client
+            .add_table(&Table {
+                id: table_id,
+                seats: 4,
+                version: 1,
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+// Prints every invariant with whether it held, false when any of them was broken
+async fn check_invariants(
+    client: &Client,
+    tables: &[u32],
+    stats: &Stats,
+    keep_tables: bool,
+) -> Result<bool, Error> {
+    println!("\nInvariants:");
+    let mut held = report(
+        "reads only returned items of the tables asked for",
+        stats.misplaced_items == 0,
+        format!("{} misplaced items", stats.misplaced_items),
+    );
+
+    let remaining = count_items(client, tables).await?;
+    if stats.uncertain_writes == 0 {
+        let expected = stats.items_added.saturating_sub(stats.items_deleted);
+        held &= report(
+            "items left match the items added minus the items deleted",
+            remaining == expected,
+            format!(
+                "{} added, {} deleted, {} left",
+                stats.items_added, stats.items_deleted, remaining
+            ),
+        );
+    } else {
+        println!(
+            "  [skip] items left match the items added minus the items deleted ({} writes with an unknown outcome)",
+            stats.uncertain_writes
+        );
+    }
+
+    if !keep_tables {
+        for &table_id in tables {
+            client.delete_table_by_id(table_id, None).await?;
+        }
+    }
+    // Across the whole database, not only the simulated tables
+    let orphaned = count_orphaned_items(client).await?;
+    held &= report(
+        "every item belongs to a table that exists",
+        orphaned == 0,
+        format!("{} orphaned items", orphaned),
+    );
+    Ok(held)
+}
+
+fn report(invariant: &str, held: bool, detail: String) -> bool {
+    if held {
+        println!("  [ok]   {}", invariant);
+    } else {
+        println!("  [FAIL] {} ({})", invariant, detail);
+    }
+    held
+}
+
+// Pages through every item on the given tables
+async fn count_items(client: &Client, tables: &[u32]) -> Result<u64, Error> {
+    let mut count = 0;
+    let mut cursor = None;
+    loop {
+        let page = client
+            .search_items(&SearchItemsRequest {
+                table_ids: Some(tables.to_vec()),
+                cursor,
+                ..Default::default()
+            })
+            .await?;
+        count += page.items.len() as u64;
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            return Ok(count);
+        }
+    }
+}
+
+// Items whose table_id has no row in tables, read from the exports
+async fn count_orphaned_items(client: &Client) -> Result<u64, Error> {
+    let tables = client.export_tables(BulkFormat::Ndjson).await?;
+    let table_ids: HashSet<u32> = parse_ndjson::<Table>(&tables)?
+        .into_iter()
+        .map(|table| table.id)
+        .collect();
+    let items = client.export_items(BulkFormat::Ndjson).await?;
+    let orphaned = parse_ndjson::<Items>(&items)?
+        .into_iter()
+        .filter(|item| !table_ids.contains(&item.table_id))
+        .count();
+    Ok(orphaned as u64)
+}
+
+fn parse_ndjson<T: DeserializeOwned>(body: &str) -> Result<Vec<T>, Error> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Error::Json))
+        .collect()
+}
@@ -0,0 +1,122 @@
+use restaurant_api_client::Error;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+const PERCENTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+// Latencies and failures of one kind of call
+#[derive(Default)]
+pub struct OpStats {
+    pub latencies: Vec<Duration>,
+    // Keyed by status code, or "no response" when the server never answered
+    pub errors: BTreeMap<String, u64>,
+}
+
+// Everything a virtual client saw, merged once all of them are done
+#[derive(Default)]
+pub struct Stats {
+    pub ops: BTreeMap<&'static str, OpStats>,
+    pub items_added: u64,
+    pub items_deleted: u64,
+    // Writes that failed without a definite answer, the server may or may not have applied them
+    pub uncertain_writes: u64,
+    // Items returned for a table, or for the simulated tables, that belong somewhere else
+    pub misplaced_items: u64,
+}
+
+impl Stats {
+    // Times `call` and counts it under `op`, the result is handed back as is
+    pub async fn record<T>(
+        &mut self,
+        op: &'static str,
+        call: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let started = Instant::now();
+        let result = call.await;
+        let op_stats = self.ops.entry(op).or_default();
+        op_stats.latencies.push(started.elapsed());
+        if let Err(err) = &result {
+            let kind = match err.status() {
+                Some(status) => status.to_string(),
+                None => "no response".to_string(),
+            };
+            *op_stats.errors.entry(kind).or_default() += 1;
+        }
+        result
+    }
+
+    // Records a failed write, only client errors are known to have changed nothing
+    pub fn failed_write(&mut self, err: &Error) {
+        if !matches!(err.status(), Some(status) if status < 500) {
+            self.uncertain_writes += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: Stats) {
+        for (op, other_stats) in other.ops {
+            let op_stats = self.ops.entry(op).or_default();
+            op_stats.latencies.extend(other_stats.latencies);
+            for (kind, count) in other_stats.errors {
+                *op_stats.errors.entry(kind).or_default() += count;
+            }
+        }
+        self.items_added += other.items_added;
+        self.items_deleted += other.items_deleted;
+        self.uncertain_writes += other.uncertain_writes;
+        self.misplaced_items += other.misplaced_items;
+    }
+
+    pub fn print_report(&mut self, elapsed: Duration) {
+        let requests: usize = self.ops.values().map(|op| op.latencies.len()).sum();
+        let errors: u64 = self.ops.values().flat_map(|op| op.errors.values()).sum();
+        println!(
+            "{} requests in {:.1}s, {:.1} req/s, {} errors\n",
+            requests,
+            elapsed.as_secs_f64(),
+            requests as f64 / elapsed.as_secs_f64(),
+            errors
+        );
+
+        println!(
+            "{:<20} {:>8} {:>8} {:>9} {:>9} {:>9} {:>9}",
+            "operation", "count", "errors", "p50", "p90", "p99", "max"
+        );
+        for (op, op_stats) in self.ops.iter_mut() {
+            op_stats.latencies.sort();
+            let errors: u64 = op_stats.errors.values().sum();
+            let mut row = format!("{:<20} {:>8} {:>8}", op, op_stats.latencies.len(), errors);
+            for percentile in PERCENTILES {
+                row.push_str(&format!(
+                    " {:>9}",
+                    format_latency(percentile_of(&op_stats.latencies, percentile))
+                ));
+            }
+            let max = op_stats.latencies.last().copied().unwrap_or_default();
+            row.push_str(&format!(" {:>9}", format_latency(max)));
+            println!("{}", row);
+        }
+
+        if errors > 0 {
+            println!("\nErrors:");
+            for (op, op_stats) in &self.ops {
+                for (kind, count) in &op_stats.errors {
+                    println!("  {} {}: {}", op, kind, count);
+                }
+            }
+        }
+    }
+}
+
+// Nearest-rank percentile of sorted latencies
+fn percentile_of(sorted: &[Duration], percentile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (percentile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn format_latency(latency: Duration) -> String {
+    format!("{:.1}ms", latency.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,254 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use restaurant_api_client::models::request::{
+    AddItemsRequest, GetItemRequest, ItemSort, SearchItemsRequest, SortDirection, TableItem,
+};
+use restaurant_api_client::Client;
+
+use crate::stats::Stats;
+
+const MENU: [&str; 8] = [
+    "Pho",
+    "Bun Cha",
+    "Banh Mi",
+    "Cha Gio",
+    "Xoi",
+    "Ca Phe Sua Da",
+    "Hanoi Beer",
+    "Tiger Beer",
+];
+const CUSTOMERS: [&str; 5] = [
+    "Barack Obama",
+    "Anthony Bourdain",
+    "Denis Chen",
+    "Walk In",
+    "Regular",
+];
+// Most orders are a few dishes, /items/add takes them in one call
+const MAX_ITEMS_PER_ORDER: usize = 4;
+// How much of a table or of the kitchen queue a read asks for
+const PAGE_SIZE: usize = 20;
+
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Add,
+    Get,
+    Delete,
+}
+
+// Relative weights of the calls a virtual client makes, e.g. "add=50,get=30,delete=20".
+// Weights left out are 0.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mix {
+    pub add: u32,
+    pub get: u32,
+    pub delete: u32,
+}
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut mix = Mix::default();
+        for pair in value
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (action, weight) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected action=weight, got {:?}", pair))?;
+            let weight = weight
+                .trim()
+                .parse()
+                .map_err(|_| format!("{:?} is not a valid weight", weight))?;
+            match action.trim() {
+                "add" => mix.add = weight,
+                "get" => mix.get = weight,
+                "delete" => mix.delete = weight,
+                other => {
+                    return Err(format!(
+                        "unknown action {:?}, expected add, get or delete",
+                        other
+                    ))
+                }
+            }
+        }
+        if mix.total() == 0 {
+            return Err("at least one weight has to be above 0".to_string());
+        }
+        Ok(mix)
+    }
+}
+
+impl Mix {
+    // Summed as u64 so weights up to u32::MAX can't overflow
+    fn total(&self) -> u64 {
+        u64::from(self.add) + u64::from(self.get) + u64::from(self.delete)
+    }
+
+    fn pick(&self, rng: &mut StdRng) -> Action {
+        let roll = rng.gen_range(0..self.total());
+        let add = u64::from(self.add);
+        if roll < add {
+            Action::Add
+        } else if roll < add + u64::from(self.get) {
+            Action::Get
+        } else {
+            Action::Delete
+        }
+    }
+}
+
+// What every virtual client shares
+#[derive(Clone)]
+pub struct Workload {
+    pub client: Client,
+    pub tables: Arc<[u32]>,
+    pub deadline: Instant,
+    // Upper bound of the random pause between two calls of the same client
+    pub think_time: Duration,
+}
+
+impl Workload {
+    async fn think(&self, rng: &mut StdRng) {
+        if !self.think_time.is_zero() {
+            tokio::time::sleep(rng.gen_range(Duration::ZERO..=self.think_time)).await;
+        }
+    }
+}
+
+// A server takes orders at the tables, checks what a table has and cancels dishes it ordered
+pub async fn run_server(workload: Workload, mix: Mix) -> Stats {
+    let mut stats = Stats::default();
+    let mut rng = StdRng::from_entropy();
+    // Dishes this server ordered, a cook may have served them already
+    let mut ordered: Vec<TableItem> = Vec::new();
+
+    while Instant::now() < workload.deadline {
+        let table_id = *workload.tables.choose(&mut rng).unwrap();
+        match mix.pick(&mut rng) {
+            Action::Add => {
+                let to_add: Vec<TableItem> = (0..rng.gen_range(1..=MAX_ITEMS_PER_ORDER))
+                    .map(|_| TableItem {
+                        table_id,
+                        item: MENU.choose(&mut rng).unwrap().to_string(),
+                        customer_id: rng
+                            .gen_bool(0.75)
+                            .then(|| CUSTOMERS.choose(&mut rng).unwrap().to_string()),
+                    })
+                    .collect();
+                let request = AddItemsRequest { to_add };
+                match stats
+                    .record("add_items", workload.client.add_items(&request))
+                    .await
+                {
+                    Ok(json_resp) => {
+                        stats.items_added += json_resp.rows.unwrap_or(0);
+                        ordered.extend(request.to_add);
+                    }
+                    Err(err) => stats.failed_write(&err),
+                }
+            }
+            Action::Get => {
+                let request = GetItemRequest {
+                    table_id,
+                    limit: Some(PAGE_SIZE),
+                    ..Default::default()
+                };
+                if let Ok(json_resp) = stats
+                    .record("get_items", workload.client.get_items(&request))
+                    .await
+                {
+                    stats.misplaced_items += json_resp
+                        .items
+                        .iter()
+                        .filter(|item| item.table_id != table_id)
+                        .count() as u64;
+                }
+            }
+            Action::Delete if !ordered.is_empty() => {
+                let item = ordered.swap_remove(rng.gen_range(0..ordered.len()));
+                match stats
+                    .record("delete_item", workload.client.delete_item(&item))
+                    .await
+                {
+                    Ok(json_resp) => stats.items_deleted += json_resp.rows.unwrap_or(0),
+                    Err(err) => stats.failed_write(&err),
+                }
+            }
+            // Nothing ordered yet, so nothing to cancel
+            Action::Delete => {}
+        }
+        workload.think(&mut rng).await;
+    }
+    stats
+}
+
+// A cook looks through the kitchen queue and serves the oldest dish of a table, which removes it
+pub async fn run_cook(workload: Workload, mix: Mix) -> Stats {
+    let mut stats = Stats::default();
+    let mut rng = StdRng::from_entropy();
+
+    while Instant::now() < workload.deadline {
+        match mix.pick(&mut rng) {
+            // Cooks never add items, the mix is checked before they start
+            Action::Add => {}
+            Action::Get => {
+                let request = SearchItemsRequest {
+                    table_ids: Some(workload.tables.to_vec()),
+                    direction: SortDirection::Asc,
+                    limit: Some(PAGE_SIZE),
+                    ..Default::default()
+                };
+                if let Ok(json_resp) = stats
+                    .record("search_items", workload.client.search_items(&request))
+                    .await
+                {
+                    stats.misplaced_items += json_resp
+                        .items
+                        .iter()
+                        .filter(|item| !workload.tables.contains(&item.table_id))
+                        .count() as u64;
+                }
+            }
+            Action::Delete => {
+                let table_id = *workload.tables.choose(&mut rng).unwrap();
+                let request = GetItemRequest {
+                    table_id,
+                    sort: ItemSort::CreatedAt,
+                    direction: SortDirection::Asc,
+                    limit: Some(1),
+                    ..Default::default()
+                };
+                let oldest = match stats
+                    .record("get_items", workload.client.get_items(&request))
+                    .await
+                {
+                    Ok(json_resp) => json_resp.items.into_iter().next(),
+                    Err(_) => None,
+                };
+                if let Some(item) = oldest {
+                    // Another cook or the server may have got there first, that deletes 0 rows
+                    match stats
+                        .record(
+                            "delete_item_by_id",
+                            workload.client.delete_item_by_id(item.id, None),
+                        )
+                        .await
+                    {
+                        Ok(json_resp) => stats.items_deleted += json_resp.rows.unwrap_or(0),
+                        Err(err) => stats.failed_write(&err),
+                    }
+                }
+            }
+        }
+        workload.think(&mut rng).await;
+    }
+    stats
+}
@@ -1,8 +1,20 @@
 /* Create tables on container start up */
+CREATE TABLE schema_migrations (
+    version INTEGER UNSIGNED PRIMARY KEY,
+    applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+INSERT INTO schema_migrations (version) VALUES (1), (2), (3), (4), (5), (6);
+
 CREATE TABLE tables (
     id INTEGER UNSIGNED PRIMARY KEY,
-    seats INTEGER UNSIGNED NOT NULL
+    seats INTEGER UNSIGNED NOT NULL,
+    version INTEGER UNSIGNED NOT NULL DEFAULT 1
+);
+/* Table versions are drawn from here so a re-created table never repeats an ETag */
+CREATE TABLE table_version_seq (
+    last_version INTEGER UNSIGNED NOT NULL
 );
+INSERT INTO table_version_seq (last_version) VALUES (1);
 CREATE TABLE items (
     id INTEGER UNSIGNED PRIMARY KEY AUTO_INCREMENT,
     table_id INTEGER UNSIGNED NOT NULL, 
@@ -10,25 +22,69 @@ CREATE TABLE items (
     cook_time TINYINT UNSIGNED NOT NULL,
     customer_id VARCHAR(90),
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    version INTEGER UNSIGNED NOT NULL DEFAULT 1,
     INDEX idx_item (item),
     INDEX idx_customer_id (customer_id),
     FOREIGN KEY (table_id) REFERENCES tables (id) 
         ON DELETE CASCADE 
         ON UPDATE CASCADE
 );
--- NOTE: sqlx will not know index and fk columns are NOT NULLABLE without explicitly setting it 
-
--- Sample inserts
-INSERT INTO tables (id, seats) VALUES (1, 4), (2, 2), (3, 5);
-
-INSERT INTO
-    items (
-        table_id, item, cook_time, customer_id 
-    )
-VALUES 
-    (1, 'Bun Cha', 10, 'Barack Obama'), 
-    (1, 'Hanoi Beer', 5, 'Barack Obama'),
-    (1, 'Bun Cha', 10, 'Anthony Bourdain'),
-    (1, 'Tiger Beer', 5, 'Anthony Bourdain'),
-    (2, 'Pho', 15, 'Denis Chen'),
-    (2, 'Pho', 15, 'Denis Chen')
\ No newline at end of file
+/* Items that were deleted once served, so reports still count them */
+CREATE TABLE item_sales (
+    item_id INTEGER UNSIGNED PRIMARY KEY,
+    table_id INTEGER UNSIGNED NOT NULL,
+    item VARCHAR(90) NOT NULL,
+    cook_time TINYINT UNSIGNED NOT NULL,
+    customer_id VARCHAR(90),
+    created_at TIMESTAMP NOT NULL,
+    sold_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    INDEX idx_created_at (created_at)
+);
+CREATE TABLE api_keys (
+    id INTEGER UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+    name VARCHAR(90) NOT NULL,
+    key_hash CHAR(64) NOT NULL,
+    scopes VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP NULL,
+    UNIQUE INDEX idx_key_hash (key_hash)
+);
+CREATE TABLE staff (
+    id INTEGER UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+    username VARCHAR(90) NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE INDEX idx_username (username)
+);
+CREATE TABLE revoked_tokens (
+    jti CHAR(32) PRIMARY KEY,
+    expires_at TIMESTAMP NOT NULL
+);
+/* Webhook secrets are kept in plaintext, they are needed to sign every delivery */
+CREATE TABLE webhook_subscriptions (
+    id INTEGER UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+    url VARCHAR(2048) NOT NULL,
+    secret VARCHAR(128) NOT NULL,
+    events VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+/* status is pending, delivered or dead (gave up after the max attempts) */
+CREATE TABLE webhook_outbox (
+    id BIGINT UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+    subscription_id INTEGER UNSIGNED NOT NULL,
+    event_type VARCHAR(40) NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    attempts INTEGER UNSIGNED NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_status_code SMALLINT UNSIGNED,
+    last_error VARCHAR(1024),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    delivered_at TIMESTAMP NULL,
+    INDEX idx_status_next_attempt (status, next_attempt_at),
+    FOREIGN KEY (subscription_id) REFERENCES webhook_subscriptions (id)
+        ON DELETE CASCADE
+);
+-- NOTE: sqlx will not know index and fk columns are NOT NULLABLE without explicitly setting it
+-- Demo data is inserted with `restaurant-admin seed`
@@ -0,0 +1,26 @@
+/* Webhook subscriptions and the outbox their deliveries are sent from.
+   Already part of init.sql, only needed for databases created before schema version 2. */
+CREATE TABLE webhook_subscriptions (
+    id INTEGER UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+    url VARCHAR(2048) NOT NULL,
+    secret VARCHAR(128) NOT NULL,
+    events VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE TABLE webhook_outbox (
+    id BIGINT UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+    subscription_id INTEGER UNSIGNED NOT NULL,
+    event_type VARCHAR(40) NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    attempts INTEGER UNSIGNED NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_status_code SMALLINT UNSIGNED,
+    last_error VARCHAR(1024),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    delivered_at TIMESTAMP NULL,
+    INDEX idx_status_next_attempt (status, next_attempt_at),
+    FOREIGN KEY (subscription_id) REFERENCES webhook_subscriptions (id)
+        ON DELETE CASCADE
+);
+INSERT INTO schema_migrations (version) VALUES (2);
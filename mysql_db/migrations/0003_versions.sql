@@ -0,0 +1,5 @@
+/* Row versions behind the ETags on tables and items.
+   Already part of init.sql, only needed for databases created before schema version 3. */
+ALTER TABLE tables ADD COLUMN version INTEGER UNSIGNED NOT NULL DEFAULT 1;
+ALTER TABLE items ADD COLUMN version INTEGER UNSIGNED NOT NULL DEFAULT 1;
+INSERT INTO schema_migrations (version) VALUES (3);
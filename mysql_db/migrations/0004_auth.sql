@@ -0,0 +1,25 @@
+/* API keys, staff accounts and revoked refresh tokens.
+   Already part of init.sql, only needed for databases created before schema version 4.
+   Databases from before schema_migrations may have them already, hence IF NOT EXISTS. */
+CREATE TABLE IF NOT EXISTS api_keys (
+    id INTEGER UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+    name VARCHAR(90) NOT NULL,
+    key_hash CHAR(64) NOT NULL,
+    scopes VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP NULL,
+    UNIQUE INDEX idx_key_hash (key_hash)
+);
+CREATE TABLE IF NOT EXISTS staff (
+    id INTEGER UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+    username VARCHAR(90) NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE INDEX idx_username (username)
+);
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    jti CHAR(32) PRIMARY KEY,
+    expires_at TIMESTAMP NOT NULL
+);
+INSERT INTO schema_migrations (version) VALUES (4);
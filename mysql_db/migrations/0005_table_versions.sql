@@ -0,0 +1,9 @@
+/* Sequence that table versions are drawn from, so a table deleted and created again under
+   the same id never repeats an ETag. Already part of init.sql, only needed for databases
+   created before schema version 5. Versions before this only grew through undo, so
+   starting well above the highest one left also clears those of deleted tables. */
+CREATE TABLE table_version_seq (
+    last_version INTEGER UNSIGNED NOT NULL
+);
+INSERT INTO table_version_seq (last_version) SELECT COALESCE(MAX(version), 0) + 1000 FROM tables;
+INSERT INTO schema_migrations (version) VALUES (5);
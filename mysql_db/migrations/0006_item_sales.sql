@@ -0,0 +1,13 @@
+/* Items that were deleted once served, so reports still count them.
+   Already part of init.sql, only needed for databases created before schema version 6. */
+CREATE TABLE item_sales (
+    item_id INTEGER UNSIGNED PRIMARY KEY,
+    table_id INTEGER UNSIGNED NOT NULL,
+    item VARCHAR(90) NOT NULL,
+    cook_time TINYINT UNSIGNED NOT NULL,
+    customer_id VARCHAR(90),
+    created_at TIMESTAMP NOT NULL,
+    sold_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    INDEX idx_created_at (created_at)
+);
+INSERT INTO schema_migrations (version) VALUES (6);
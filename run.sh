@@ -18,7 +18,9 @@ fi
 docker-compose up --build --force-recreate --always-recreate-deps -d
 cargo build --release
 
-echo "Waiting for the database container to start..."
-sleep 12 # Quick hack to wait for the database container to start, should be replaced with a proper health check
+if [ "$1" = "-f" ]; then
+    # The new database only has the schema, add the demo data
+    cargo run --release --bin restaurant-admin -- seed
+fi
 
 cargo run --release
\ No newline at end of file
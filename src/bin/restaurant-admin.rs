@@ -0,0 +1,500 @@
+// Operations tool for the restaurant database. Reads the same config as the server and talks to
+// the database directly, so changes made here publish no stream events and can't be undone
+// through the API. Imports still queue their webhooks, which are written with the rows.
+use chrono::{DateTime, Utc};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use dotenv::dotenv;
+use sqlx::mysql::{MySql, MySqlPool};
+use sqlx::QueryBuilder;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tokio_stream::StreamExt;
+
+use restaurant_api::models::database::Items;
+use restaurant_api::models::request::{BulkFormat, ImportMode};
+use restaurant_api::utils::bulk;
+use restaurant_api::utils::config::{Cli as ServerCli, Config};
+use restaurant_api::utils::database_connection::{
+    database_connect, wait_for_database, AppDatabase, EXPECTED_SCHEMA_VERSION,
+};
+use restaurant_api::utils::etag::next_table_version;
+use restaurant_api::utils::migrations::{migrate, schema_version, Migrated};
+
+// Demo tables as (id, seats)
+const DEMO_TABLES: [(u32, u32); 3] = [(1, 4), (2, 2), (3, 5)];
+// Demo items as (table id, item, cook time, customer id)
+const DEMO_ITEMS: [(u32, &str, u8, &str); 6] = [
+    (1, "Bun Cha", 10, "Barack Obama"),
+    (1, "Hanoi Beer", 5, "Barack Obama"),
+    (1, "Bun Cha", 10, "Anthony Bourdain"),
+    (1, "Tiger Beer", 5, "Anthony Bourdain"),
+    (2, "Pho", 15, "Denis Chen"),
+    (2, "Pho", 15, "Denis Chen"),
+];
+const DEFAULT_ITEMS_LIMIT: u32 = 50;
+// Deleted items are copied into item_sales like the API does, followed by the same filter
+// as the delete
+const RECORD_SALES_SQL: &str = "INSERT INTO item_sales (item_id, table_id, item, cook_time, customer_id, created_at) SELECT id, table_id, item, cook_time, customer_id, created_at FROM items";
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "restaurant-admin",
+    about = "Manage the restaurant database with the server's config"
+)]
+struct Cli {
+    /// Path to a TOML config file (defaults to config.toml when present)
+    #[arg(long, value_name = "PATH", global = true)]
+    config: Option<PathBuf>,
+    #[arg(long, value_name = "URL", global = true)]
+    database_url: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List, create and delete tables
+    #[command(subcommand)]
+    Tables(TablesCommand),
+    /// Inspect and purge items
+    #[command(subcommand)]
+    Items(ItemsCommand),
+    /// Create the schema in an empty database or apply the migrations it is missing
+    Migrate {
+        /// Only print the schema version
+        #[arg(long)]
+        status: bool,
+    },
+    /// Insert the demo tables and items
+    Seed,
+    /// Stream every table or item as CSV or newline delimited JSON
+    Export {
+        #[arg(value_enum)]
+        resource: Resource,
+        #[arg(long, value_parser = parse_format, default_value = "ndjson")]
+        format: BulkFormat,
+        /// File to write to instead of stdout
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Import tables or items from an export, import tables before their items
+    Import {
+        #[arg(value_enum)]
+        resource: Resource,
+        /// File to read, - for stdin
+        file: PathBuf,
+        #[arg(long, value_parser = parse_format, default_value = "ndjson")]
+        format: BulkFormat,
+        /// all_or_nothing imports nothing when any row fails, best_effort imports the valid rows
+        #[arg(long, value_parser = parse_mode, default_value = "all_or_nothing")]
+        mode: ImportMode,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Resource {
+    Tables,
+    Items,
+}
+
+#[derive(Debug, Subcommand)]
+enum TablesCommand {
+    /// List tables with their number of items
+    List,
+    Create {
+        id: u32,
+        #[arg(long, default_value_t = 1)]
+        seats: u32,
+    },
+    /// Delete a table along with its items
+    Delete { id: u32 },
+}
+
+#[derive(Debug, Subcommand)]
+enum ItemsCommand {
+    /// List items, oldest first
+    List {
+        #[arg(long, value_name = "ID")]
+        table: Option<u32>,
+        #[arg(long)]
+        item: Option<String>,
+        #[arg(long, value_name = "ID")]
+        customer: Option<String>,
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_ITEMS_LIMIT)]
+        limit: u32,
+    },
+    /// Delete the items matching every filter given
+    #[command(group(
+        ArgGroup::new("filter")
+            .required(true)
+            .multiple(true)
+            .args(["table", "before", "all"])
+    ))]
+    Purge {
+        #[arg(long, value_name = "ID")]
+        table: Option<u32>,
+        /// Only items created before this time, e.g. 2024-01-31T00:00:00Z
+        #[arg(long, value_name = "RFC3339")]
+        before: Option<DateTime<Utc>>,
+        /// Purge every item instead of filtering
+        #[arg(long, conflicts_with_all = ["table", "before"])]
+        all: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+    let cli = Cli::parse();
+    let config = match Config::load_for_cli(&ServerCli {
+        config: cli.config.clone(),
+        database_url: cli.database_url.clone(),
+        ..Default::default()
+    }) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
+        }
+    };
+
+    let app_database = match database_connect(&config.database) {
+        Ok(app_database) => app_database,
+        Err(err) => {
+            eprintln!("Invalid database configuration: {}", err);
+            return ExitCode::from(2);
+        }
+    };
+    if let Err(err) = wait_for_database(&app_database, &config.database).await {
+        eprintln!("Could not connect to the database: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    let pool = &app_database.connection_pool;
+    let result = match cli.command {
+        Command::Tables(command) => run_tables(pool, command).await,
+        Command::Items(command) => run_items(pool, command).await,
+        Command::Migrate { status } => run_migrate(pool, status).await,
+        Command::Seed => seed(pool).await,
+        Command::Export {
+            resource,
+            format,
+            output,
+        } => export(pool, resource, format, output).await,
+        Command::Import {
+            resource,
+            file,
+            format,
+            mode,
+        } => import(&app_database, resource, &file, format, mode).await,
+    };
+    pool.close().await;
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run_tables(pool: &MySqlPool, command: TablesCommand) -> Result<(), String> {
+    match command {
+        TablesCommand::List => {
+            let tables: Vec<(u32, u32, u32, i64)> = sqlx::query_as(
+                "SELECT tables.id, tables.seats, tables.version, COUNT(items.id) FROM tables LEFT JOIN items ON items.table_id = tables.id GROUP BY tables.id, tables.seats, tables.version ORDER BY tables.id",
+            )
+            .fetch_all(pool)
+            .await
+            .map_err(|err| format!("Failed to list tables: {}", err))?;
+
+            println!("{:>8} {:>6} {:>6} {:>8}", "id", "seats", "items", "version");
+            for (id, seats, version, items) in &tables {
+                println!("{:>8} {:>6} {:>6} {:>8}", id, seats, items, version);
+            }
+            println!("{} tables", tables.len());
+        }
+
+        TablesCommand::Create { id, seats } => {
+            let create_err = |err: sqlx::Error| format!("Failed to create table {}: {}", id, err);
+            let mut tx = pool.begin().await.map_err(create_err)?;
+            let version = next_table_version(&mut tx).await.map_err(create_err)?;
+            sqlx::query("INSERT INTO tables (id, seats, version) VALUES (?, ?, ?)")
+                .bind(id)
+                .bind(seats)
+                .bind(version)
+                .execute(&mut *tx)
+                .await
+                .map_err(create_err)?;
+            tx.commit().await.map_err(create_err)?;
+            println!("Created table {} with {} seats", id, seats);
+        }
+
+        TablesCommand::Delete { id } => {
+            let delete_err = |err: sqlx::Error| format!("Failed to delete table {}: {}", id, err);
+            let mut tx = pool.begin().await.map_err(delete_err)?;
+            let mut sales = QueryBuilder::<MySql>::new(RECORD_SALES_SQL);
+            sales.push(" WHERE table_id = ").push_bind(id);
+            sales.build().execute(&mut *tx).await.map_err(delete_err)?;
+            let result = sqlx::query("DELETE FROM tables WHERE id = ?")
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(delete_err)?;
+            if result.rows_affected() == 0 {
+                return Err(format!("Table {} does not exist", id));
+            }
+            tx.commit().await.map_err(delete_err)?;
+            println!("Deleted table {} and its items", id);
+        }
+    }
+    Ok(())
+}
+
+async fn run_items(pool: &MySqlPool, command: ItemsCommand) -> Result<(), String> {
+    match command {
+        ItemsCommand::List {
+            table,
+            item,
+            customer,
+            limit,
+        } => {
+            let mut query = QueryBuilder::<MySql>::new("SELECT * FROM items WHERE 1 = 1");
+            if let Some(table) = table {
+                query.push(" AND table_id = ").push_bind(table);
+            }
+            if let Some(item) = item {
+                query.push(" AND item = ").push_bind(item);
+            }
+            if let Some(customer) = customer {
+                query.push(" AND customer_id = ").push_bind(customer);
+            }
+            query.push(" ORDER BY id LIMIT ").push_bind(limit);
+            let items: Vec<Items> = query
+                .build_query_as()
+                .fetch_all(pool)
+                .await
+                .map_err(|err| format!("Failed to list items: {}", err))?;
+
+            println!(
+                "{:>8} {:>8} {:<24} {:<24} {:>9}  created at",
+                "id", "table", "item", "customer", "cook time"
+            );
+            for item in &items {
+                println!(
+                    "{:>8} {:>8} {:<24} {:<24} {:>9}  {}",
+                    item.id,
+                    item.table_id,
+                    item.item,
+                    item.customer_id.as_deref().unwrap_or("-"),
+                    item.cook_time,
+                    item.created_at.to_rfc3339()
+                );
+            }
+            println!("{} items (limit {})", items.len(), limit);
+        }
+
+        ItemsCommand::Purge { table, before, .. } => {
+            let purge_err = |err: sqlx::Error| format!("Failed to purge items: {}", err);
+            let mut tx = pool.begin().await.map_err(purge_err)?;
+            let mut sales = QueryBuilder::<MySql>::new(RECORD_SALES_SQL);
+            let mut query = QueryBuilder::<MySql>::new("DELETE FROM items");
+            for builder in [&mut sales, &mut query] {
+                builder.push(" WHERE 1 = 1");
+                if let Some(table) = table {
+                    builder.push(" AND table_id = ").push_bind(table);
+                }
+                if let Some(before) = before {
+                    builder.push(" AND created_at < ").push_bind(before);
+                }
+            }
+            sales.build().execute(&mut *tx).await.map_err(purge_err)?;
+            let result = query.build().execute(&mut *tx).await.map_err(purge_err)?;
+            tx.commit().await.map_err(purge_err)?;
+            println!("Purged {} items", result.rows_affected());
+        }
+    }
+    Ok(())
+}
+
+async fn run_migrate(pool: &MySqlPool, status: bool) -> Result<(), String> {
+    let version = schema_version(pool)
+        .await
+        .map_err(|err| format!("Failed to read the schema version: {}", err))?;
+    if status {
+        match version {
+            Some(version) => println!(
+                "At schema version {}, the server expects {}",
+                version, EXPECTED_SCHEMA_VERSION
+            ),
+            None => println!(
+                "No schema yet, the server expects version {}",
+                EXPECTED_SCHEMA_VERSION
+            ),
+        }
+        return Ok(());
+    }
+
+    match migrate(pool)
+        .await
+        .map_err(|err| format!("Migration failed: {}", err))?
+    {
+        Migrated::Created => println!("Created the schema at version {}", EXPECTED_SCHEMA_VERSION),
+        Migrated::Baselined { baseline, applied } => {
+            println!("Recorded the existing schema at version {}", baseline);
+            for version in applied {
+                println!("Applied migration {}", version);
+            }
+        }
+        Migrated::Applied(applied) if applied.is_empty() => {
+            println!("Already at schema version {}", version.unwrap_or_default())
+        }
+        Migrated::Applied(applied) => {
+            for version in applied {
+                println!("Applied migration {}", version);
+            }
+        }
+    }
+    Ok(())
+}
+
+// All or nothing, fails when any of the demo tables already exists
+async fn seed(pool: &MySqlPool) -> Result<(), String> {
+    let seed_err = |err: sqlx::Error| format!("Failed to seed demo data: {}", err);
+    let mut tx = pool.begin().await.map_err(seed_err)?;
+    for (id, seats) in DEMO_TABLES {
+        let version = next_table_version(&mut tx).await.map_err(seed_err)?;
+        sqlx::query("INSERT INTO tables (id, seats, version) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(seats)
+            .bind(version)
+            .execute(&mut *tx)
+            .await
+            .map_err(seed_err)?;
+    }
+    for (table_id, item, cook_time, customer_id) in DEMO_ITEMS {
+        sqlx::query(
+            "INSERT INTO items (table_id, item, cook_time, customer_id) VALUES (?, ?, ?, ?)",
+        )
+        .bind(table_id)
+        .bind(item)
+        .bind(cook_time)
+        .bind(customer_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(seed_err)?;
+    }
+    tx.commit().await.map_err(seed_err)?;
+    println!(
+        "Seeded {} tables and {} items",
+        DEMO_TABLES.len(),
+        DEMO_ITEMS.len()
+    );
+    Ok(())
+}
+
+// Rows are written as they are read, so large exports don't have to fit in memory
+async fn export(
+    pool: &MySqlPool,
+    resource: Resource,
+    format: BulkFormat,
+    output: Option<PathBuf>,
+) -> Result<(), String> {
+    let mut lines = match resource {
+        Resource::Tables => bulk::export_tables(pool.clone(), format),
+        Resource::Items => bulk::export_items(pool.clone(), format),
+    };
+    let mut writer: Box<dyn Write> = match &output {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).map_err(|err| {
+                format!("Failed to create {}: {}", path.display(), err)
+            })?))
+        }
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+
+    let write_err = |err: std::io::Error| format!("Failed to write: {}", err);
+    // Counts the CSV header too
+    let mut written = 0;
+    while let Some(line) = lines.next().await {
+        let line = line.map_err(|err| format!("Failed to export: {}", err))?;
+        writer.write_all(line.as_bytes()).map_err(write_err)?;
+        written += 1;
+    }
+    writer.flush().map_err(write_err)?;
+
+    if let Some(path) = output {
+        if format == BulkFormat::Csv {
+            written -= 1;
+        }
+        eprintln!(
+            "Exported {} {} to {}",
+            written,
+            resource.as_str(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+async fn import(
+    app_database: &AppDatabase,
+    resource: Resource,
+    file: &Path,
+    format: BulkFormat,
+    mode: ImportMode,
+) -> Result<(), String> {
+    let body = if file == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        std::fs::read_to_string(file)
+    }
+    .map_err(|err| format!("Failed to read {}: {}", file.display(), err))?;
+
+    // The server's subscribers can't be reached from here, see the note at the top
+    let report = match resource {
+        Resource::Tables => bulk::import_tables(app_database, &body, format, mode, |_| {}).await,
+        Resource::Items => bulk::import_items(app_database, &body, format, mode, |_| {}).await,
+    }
+    .map_err(|err| format!("Failed to import {}: {}", resource.as_str(), err))?;
+
+    for err in &report.errors {
+        eprintln!("line {}: {}", err.line, err.msg);
+    }
+    if report.failed > report.errors.len() as u64 {
+        eprintln!(
+            "... and {} more errors",
+            report.failed - report.errors.len() as u64
+        );
+    }
+    println!(
+        "Imported {} {}, {} failed",
+        report.imported,
+        resource.as_str(),
+        report.failed
+    );
+    if report.failed > 0 {
+        return Err(format!("{} rows failed to import", report.failed));
+    }
+    Ok(())
+}
+
+impl Resource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Resource::Tables => "tables",
+            Resource::Items => "items",
+        }
+    }
+}
+
+fn parse_format(format: &str) -> Result<BulkFormat, String> {
+    BulkFormat::parse(format).ok_or_else(|| "expected csv or ndjson".to_string())
+}
+
+fn parse_mode(mode: &str) -> Result<ImportMode, String> {
+    ImportMode::parse(mode).ok_or_else(|| "expected all_or_nothing or best_effort".to_string())
+}
@@ -0,0 +1,136 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::database::ApiKey;
+use crate::models::request::CreateApiKeyRequest;
+use crate::models::response::{ApiKeysResponse, CreateApiKeyResponse, GenericResponse};
+use crate::utils::auth::{
+    generate_api_key, hash_api_key, scopes_to_string, AdminScope, Authorized,
+};
+use crate::utils::response_builder::{ApiKeyErrorResponseBuilder, ApiKeySuccessResponseBuilder};
+use crate::AppDatabase;
+
+#[utoipa::path(
+    put,
+    path = "/admin/keys/add",
+    tag = "admin",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 200, description = "Key created, the plaintext key is only returned here", body = CreateApiKeyResponse),
+        (status = 400, description = "No scopes given", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn create_api_key(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Json(body): Json<CreateApiKeyRequest>,
+) -> Response {
+    if body.scopes.is_empty() {
+        return GenericResponse {
+            msg: "An API key needs at least one scope".to_string(),
+            status_code: StatusCode::BAD_REQUEST.as_u16(),
+            rows: None,
+        }
+        .into_response();
+    }
+
+    // Only the hash is stored, the plaintext key is returned to the caller exactly once
+    let key = generate_api_key();
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query("INSERT INTO api_keys (name, key_hash, scopes) VALUES (?, ?, ?)")
+            .bind(&body.name)
+            .bind(hash_api_key(&key))
+            .bind(scopes_to_string(&body.scopes))
+            .execute(&mut *connection)
+            .await
+    }
+    .await;
+    match result {
+        Ok(result) => Json(CreateApiKeyResponse {
+            id: result.last_insert_id(),
+            name: body.name,
+            scopes: body.scopes,
+            key,
+        })
+        .into_response(),
+
+        Err(err) => {
+            let err_resp = err.create_api_key_err(body);
+            error!(handler = "create_api_key", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/keys",
+    tag = "admin",
+    responses((status = 200, description = "Every API key", body = ApiKeysResponse)),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn get_api_keys(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query_as::<_, ApiKey>(
+            "SELECT id, name, scopes, created_at, revoked_at FROM api_keys ORDER BY id",
+        )
+        .fetch_all(&mut *connection)
+        .await
+    }
+    .await;
+    match result {
+        Ok(keys) => Json(ApiKeysResponse { keys }).into_response(),
+
+        Err(err) => {
+            let err_resp = err.get_api_keys_err();
+            error!(handler = "get_api_keys", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/keys/delete/{id}",
+    tag = "admin",
+    params(("id" = u32, Path, description = "API key id")),
+    responses((status = 200, description = "Key revoked", body = GenericResponse)),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn revoke_api_key(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Path(id): Path<u32>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query(
+            "UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP WHERE id = ? AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .execute(&mut *connection)
+        .await
+    }
+    .await;
+    match result {
+        Ok(results) => results.revoke_api_key_response(id),
+
+        Err(err) => {
+            let err_resp = err.revoke_api_key_err(id);
+            error!(handler = "revoke_api_key", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
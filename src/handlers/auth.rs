@@ -0,0 +1,211 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::database::Role;
+use crate::models::request::{LoginRequest, LogoutRequest, RefreshRequest};
+use crate::models::response::GenericResponse;
+use crate::utils::auth::{dummy_password_hash, verify_password, Principal};
+use crate::utils::jwt::{is_revoked, revoke, Claims, JwtKeys, TokenType};
+use crate::utils::response_builder::{
+    invalid_login_response, invalid_refresh_token_response, logout_response, token_err,
+    AuthErrorResponseBuilder,
+};
+use crate::AppDatabase;
+
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Access and refresh tokens", body = TokenResponse),
+        (status = 401, description = "Unknown user or wrong password", body = GenericResponse)
+    )
+)]
+pub async fn login(
+    State(app_database): State<Arc<AppDatabase>>,
+    State(jwt_keys): State<Arc<JwtKeys>>,
+    Json(body): Json<LoginRequest>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query_as("SELECT id, role, password_hash FROM staff WHERE username = ?")
+            .bind(&body.username)
+            .fetch_optional(&mut *connection)
+            .await
+    }
+    .await;
+    let staff: Option<(u32, String, String)> = match result {
+        Ok(staff) => staff,
+
+        Err(err) => {
+            let err_resp = err.login_err(&body.username);
+            error!(handler = "login", error = %err, "{}", err_resp.msg);
+            return err_resp.into_response();
+        }
+    };
+
+    let (staff, password_hash) = match staff {
+        Some((staff_id, role, password_hash)) => (Some((staff_id, role)), Some(password_hash)),
+        None => (None, None),
+    };
+
+    // Argon2 is deliberately slow, keep it off the async workers. Unknown usernames are checked
+    // against a dummy hash so they take as long as a wrong password
+    let password = body.password.clone();
+    let verified = tokio::task::spawn_blocking(move || match &password_hash {
+        Some(password_hash) => verify_password(&password, password_hash),
+        None => verify_password(&password, dummy_password_hash()),
+    })
+    .await
+    .unwrap_or(false);
+
+    let role = staff.and_then(|(staff_id, role)| Some((staff_id, Role::parse(&role)?)));
+    match (verified, role) {
+        (true, Some((staff_id, role))) => issue_tokens(&jwt_keys, staff_id, &body.username, role),
+        _ => invalid_login_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "A new token pair", body = TokenResponse),
+        (status = 401, description = "Invalid, expired or revoked refresh token", body = GenericResponse)
+    )
+)]
+pub async fn refresh(
+    State(app_database): State<Arc<AppDatabase>>,
+    State(jwt_keys): State<Arc<JwtKeys>>,
+    Json(body): Json<RefreshRequest>,
+) -> Response {
+    let claims = match jwt_keys.verify(&body.refresh_token, TokenType::Refresh) {
+        Some(claims) => claims,
+        None => return invalid_refresh_token_response(),
+    };
+
+    match rotate_refresh_token(&app_database, &claims).await {
+        Ok(Some((username, role))) => issue_tokens(&jwt_keys, claims.sub, &username, role),
+        Ok(None) => invalid_refresh_token_response(),
+
+        Err(err) => {
+            let err_resp = err.refresh_err();
+            error!(handler = "refresh", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "auth",
+    request_body(content = Option<LogoutRequest>, description = "Optionally revoke the refresh token too"),
+    responses(
+        (status = 200, description = "Tokens revoked", body = GenericResponse),
+        (status = 400, description = "Logged in with an API key rather than a token", body = GenericResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn logout(
+    State(app_database): State<Arc<AppDatabase>>,
+    State(jwt_keys): State<Arc<JwtKeys>>,
+    Extension(principal): Extension<Principal>,
+    body: Option<Json<LogoutRequest>>,
+) -> Response {
+    let claims = match principal {
+        Principal::Staff(claims) => claims,
+        Principal::ApiKey { .. } => {
+            return GenericResponse {
+                msg: "Only staff sessions can be logged out, revoke API keys instead".to_string(),
+                status_code: StatusCode::BAD_REQUEST.as_u16(),
+                rows: None,
+            }
+            .into_response()
+        }
+    };
+
+    // Optionally revoke the refresh token too, as long as it belongs to the same staff member
+    let mut to_revoke = vec![claims.clone()];
+    if let Some(Json(LogoutRequest {
+        refresh_token: Some(refresh_token),
+    })) = body
+    {
+        if let Some(refresh_claims) = jwt_keys
+            .verify(&refresh_token, TokenType::Refresh)
+            .filter(|refresh_claims| refresh_claims.sub == claims.sub)
+        {
+            to_revoke.push(refresh_claims);
+        }
+    }
+
+    let mut revoked = 0;
+    for claims in &to_revoke {
+        let result = async {
+            let mut connection = app_database.acquire().await?;
+            revoke(&mut connection, claims).await
+        }
+        .await;
+        match result {
+            Ok(true) => revoked += 1,
+            Ok(false) => {}
+
+            Err(err) => {
+                let err_resp = err.logout_err();
+                error!(handler = "logout", error = %err, "{}", err_resp.msg);
+                return err_resp.into_response();
+            }
+        }
+    }
+    logout_response(revoked)
+}
+
+// Refresh tokens are single use: the presented token is revoked before a new pair is issued.
+// The role is re-read so role changes and deleted accounts take effect on the next refresh.
+async fn rotate_refresh_token(
+    app_database: &AppDatabase,
+    claims: &Claims,
+) -> Result<Option<(String, Role)>, sqlx::Error> {
+    let mut connection = app_database.acquire().await?;
+    if is_revoked(&mut connection, &claims.jti).await? {
+        return Ok(None);
+    }
+
+    let staff: Option<(String, String)> =
+        sqlx::query_as("SELECT username, role FROM staff WHERE id = ?")
+            .bind(claims.sub)
+            .fetch_optional(&mut *connection)
+            .await?;
+    let (username, role) =
+        match staff.and_then(|(username, role)| Some((username, Role::parse(&role)?))) {
+            Some(staff) => staff,
+            None => return Ok(None),
+        };
+
+    // Losing the race against a concurrent refresh with the same token counts as revoked
+    if !revoke(&mut connection, claims).await? {
+        return Ok(None);
+    }
+    Ok(Some((username, role)))
+}
+
+fn issue_tokens(jwt_keys: &JwtKeys, staff_id: u32, username: &str, role: Role) -> Response {
+    match jwt_keys.issue_pair(staff_id, username, role) {
+        Ok(tokens) => Json(tokens).into_response(),
+
+        Err(err) => {
+            let err_resp = token_err(username);
+            error!(handler = "issue_tokens", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
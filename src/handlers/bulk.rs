@@ -0,0 +1,142 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::request::{BulkFormat, ExportQuery, ImportQuery};
+use crate::utils::auth::{AdminScope, Authorized};
+use crate::utils::bulk::{self, ExportStream};
+use crate::utils::events::EventBus;
+use crate::utils::response_builder::{BulkErrorResponseBuilder, ImportSuccessResponseBuilder};
+use crate::AppDatabase;
+
+// Exports are streamed, a database error part way through cuts the response short.
+// Imports publish TableAdded and ItemAdded for every row they commit, like the single row routes,
+// and queue the webhooks for them in the same transaction.
+#[utoipa::path(
+    get,
+    path = "/admin/export/tables",
+    tag = "admin",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Every table as CSV or newline delimited JSON", body = String)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn export_tables(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let lines = bulk::export_tables(app_database.connection_pool.clone(), query.format);
+    export_response("tables", query.format, lines)
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/export/items",
+    tag = "admin",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Every item as CSV or newline delimited JSON", body = String)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn export_items(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let lines = bulk::export_items(app_database.connection_pool.clone(), query.format);
+    export_response("items", query.format, lines)
+}
+
+fn export_response(resource: &str, format: BulkFormat, lines: ExportStream) -> Response {
+    (
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", resource, format.as_str()),
+            ),
+        ],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+// Tables keep their ids, existing ids are errors
+#[utoipa::path(
+    put,
+    path = "/admin/import/tables",
+    tag = "admin",
+    params(ImportQuery),
+    request_body(content = String, description = "Rows with id and seats, as exported", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Rows imported, any that failed are listed", body = ImportReport),
+        (status = 422, description = "Nothing imported, the errors are listed by line", body = ImportReport),
+        (status = 413, description = "Body is over the size limit, import large files with restaurant-admin", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn import_tables(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    State(event_bus): State<Arc<EventBus>>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Response {
+    match bulk::import_tables(&app_database, &body, query.format, query.mode, |event| {
+        event_bus.publish(event)
+    })
+    .await
+    {
+        Ok(report) => report.import_response(),
+
+        Err(err) => {
+            let err_resp = err.import_err("tables");
+            error!(handler = "import_tables", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+// Items get new ids and need their tables to exist
+#[utoipa::path(
+    put,
+    path = "/admin/import/items",
+    tag = "admin",
+    params(ImportQuery),
+    request_body(content = String, description = "Rows with table_id and item, optionally cook_time, customer_id and created_at", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Rows imported, any that failed are listed", body = ImportReport),
+        (status = 422, description = "Nothing imported, the errors are listed by line", body = ImportReport),
+        (status = 413, description = "Body is over the size limit, import large files with restaurant-admin", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn import_items(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    State(event_bus): State<Arc<EventBus>>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Response {
+    match bulk::import_items(&app_database, &body, query.format, query.mode, |event| {
+        event_bus.publish(event)
+    })
+    .await
+    {
+        Ok(report) => report.import_response(),
+
+        Err(err) => {
+            let err_resp = err.import_err("items");
+            error!(handler = "import_items", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
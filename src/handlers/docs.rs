@@ -0,0 +1,208 @@
+use axum::{
+    extract::Path,
+    http::header::{CACHE_CONTROL, CONTENT_TYPE},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::handlers::{
+    api_keys, auth, bulk, events, health_check, items, metrics, reports, staff, tables, undo,
+    webhooks,
+};
+use crate::models::{database, request, response};
+use crate::utils::auth::API_KEY_HEADER;
+use crate::utils::response_builder::docs_asset_not_found_response;
+
+// Swagger UI page. Its assets are swagger-ui-dist 5.17.14, vendored in swagger-ui/ with their
+// license and served by get_docs_asset, so the docs work without reaching a CDN.
+const DOCS_PAGE: &str = include_str!("docs.html");
+const SWAGGER_UI_BUNDLE: &str = include_str!("swagger-ui/swagger-ui-bundle.js");
+const SWAGGER_UI_CSS: &str = include_str!("swagger-ui/swagger-ui.css");
+
+// The OpenAPI document is generated from the models and the #[utoipa::path] annotations on each
+// handler. Every route registered in lib.rs needs to be listed under paths, the tests check this.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Restaurant API",
+        description = "Tables, items and the staff and integrations that manage them"
+    ),
+    paths(
+        health_check::liveness_checker,
+        health_check::readiness_checker,
+        metrics::get_metrics,
+        get_openapi,
+        get_docs,
+        get_docs_asset,
+        auth::login,
+        auth::refresh,
+        auth::logout,
+        tables::get_seats,
+        tables::add_table,
+        tables::delete_table_by_id,
+        undo::undo_table,
+        items::get_items,
+        items::search_items,
+        items::get_item,
+        items::add_items,
+        items::delete_item,
+        items::delete_item_by_id,
+        api_keys::get_api_keys,
+        api_keys::create_api_key,
+        api_keys::revoke_api_key,
+        staff::get_staff,
+        staff::create_staff,
+        staff::delete_staff,
+        webhooks::get_webhooks,
+        webhooks::create_webhook,
+        webhooks::delete_webhook,
+        webhooks::get_dead_letters,
+        webhooks::retry_dead_letter,
+        bulk::export_tables,
+        bulk::export_items,
+        bulk::import_tables,
+        bulk::import_items,
+        reports::get_report,
+        events::events_ws,
+        events::events_sse,
+    ),
+    components(schemas(
+        database::Table,
+        database::Items,
+        database::Scope,
+        database::ApiKey,
+        database::Role,
+        database::Staff,
+        database::WebhookSubscription,
+        database::WebhookDelivery,
+        request::GetItemRequest,
+        request::ItemSort,
+        request::SortDirection,
+        request::SearchItemsRequest,
+        request::ItemMatch,
+        request::TableItem,
+        request::AddItemsRequest,
+        request::CreateApiKeyRequest,
+        request::CreateStaffRequest,
+        request::LoginRequest,
+        request::RefreshRequest,
+        request::LogoutRequest,
+        request::SubscribeRequest,
+        request::CreateWebhookRequest,
+        request::BulkFormat,
+        request::ImportMode,
+        request::ReportGroup,
+        request::ReportFormat,
+        response::GenericResponse,
+        response::GetSeatsResponse,
+        response::ItemsResponse,
+        response::CreateApiKeyResponse,
+        response::ApiKeysResponse,
+        response::StaffResponse,
+        response::TokenResponse,
+        response::ComponentStatus,
+        response::ReadinessResponse,
+        response::CreateWebhookResponse,
+        response::WebhooksResponse,
+        response::DeadLettersResponse,
+        response::ImportReport,
+        response::ImportError,
+        response::ReportResponse,
+        response::ReportRow,
+        response::DomainEvent,
+        response::EventMessage,
+        response::StreamMessage,
+    )),
+    modifiers(&SecuritySchemes, &NoLicense),
+    tags(
+        (name = "health", description = "Liveness and readiness checks"),
+        (name = "metrics", description = "Prometheus metrics"),
+        (name = "docs", description = "This document"),
+        (name = "auth", description = "Staff sessions"),
+        (name = "tables", description = "Tables and undoing deletes"),
+        (name = "items", description = "Items on tables"),
+        (name = "admin", description = "API keys, staff accounts, webhooks, bulk import and export and reports"),
+        (name = "events", description = "Live streams of table and item changes"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+            );
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
+
+// Cargo.toml has no license, which would otherwise show up as a license with an empty name
+struct NoLicense;
+
+impl Modify for NoLicense {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.info.license = None;
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "docs",
+    responses((status = 200, description = "OpenAPI 3 document for this API", content_type = "application/json"))
+)]
+pub async fn get_openapi() -> Response {
+    Json(ApiDoc::openapi()).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/docs",
+    tag = "docs",
+    responses((status = 200, description = "Interactive docs page", content_type = "text/html"))
+)]
+pub async fn get_docs() -> Response {
+    Html(DOCS_PAGE).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/docs/{asset}",
+    tag = "docs",
+    params(("asset" = String, Path, description = "swagger-ui-bundle.js or swagger-ui.css")),
+    responses(
+        (status = 200, description = "Script or stylesheet of the docs page"),
+        (status = 404, description = "No such asset", body = GenericResponse)
+    )
+)]
+pub async fn get_docs_asset(Path(asset): Path<String>) -> Response {
+    let (content_type, body) = match asset.as_str() {
+        "swagger-ui-bundle.js" => ("text/javascript", SWAGGER_UI_BUNDLE),
+        "swagger-ui.css" => ("text/css", SWAGGER_UI_CSS),
+        _ => return docs_asset_not_found_response(&asset),
+    };
+    // The assets only change with the binary
+    (
+        [
+            (CONTENT_TYPE, content_type),
+            (CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        body,
+    )
+        .into_response()
+}
@@ -0,0 +1,238 @@
+use axum::extract::ws::{close_code, CloseFrame};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_stream::StreamExt;
+use tracing::{debug, warn};
+
+use crate::models::request::{EventsQuery, SubscribeRequest};
+use crate::models::response::{EventMessage, StreamMessage};
+use crate::utils::auth::{Authorized, ReadScope};
+use crate::utils::events::{EventBus, Subscription};
+use crate::utils::response_builder::invalid_subscription_response;
+use crate::utils::shutdown::{InFlight, InFlightGuard};
+
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+// GET /events/ws?tables=1,2 streams events for those tables, without `tables` for the whole kitchen.
+// Clients can change their subscription by sending {"tables": [..]} or {"tables": null}.
+#[utoipa::path(
+    get,
+    path = "/events/ws",
+    tag = "events",
+    params(EventsQuery),
+    responses(
+        (status = 101, description = "WebSocket of EventMessage and StreamMessage JSON frames"),
+        (status = 400, description = "Invalid table list", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn events_ws(
+    _: Authorized<ReadScope>,
+    State(event_bus): State<Arc<EventBus>>,
+    State(in_flight): State<Arc<InFlight>>,
+    Query(query): Query<EventsQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    match Subscription::parse(query.tables.as_deref()) {
+        Ok(subscription) => ws.on_upgrade(move |socket| {
+            stream_events(
+                socket,
+                event_bus,
+                subscription,
+                in_flight.enter(),
+                in_flight,
+            )
+        }),
+        Err(reason) => invalid_subscription_response(reason),
+    }
+}
+
+// GET /events/sse?tables=1,2, the same events as /events/ws as Server-Sent Events.
+// A reconnecting client sends Last-Event-ID and first gets the buffered events it missed.
+#[utoipa::path(
+    get,
+    path = "/events/sse",
+    tag = "events",
+    params(
+        EventsQuery,
+        ("last-event-id" = Option<u64>, Header, description = "Replay the events after this id")
+    ),
+    responses(
+        (status = 200, description = "Stream of EventMessage events", content_type = "text/event-stream", body = EventMessage),
+        (status = 400, description = "Invalid table list or Last-Event-ID", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn events_sse(
+    _: Authorized<ReadScope>,
+    State(event_bus): State<Arc<EventBus>>,
+    State(in_flight): State<Arc<InFlight>>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let subscription = match Subscription::parse(query.tables.as_deref()) {
+        Ok(subscription) => subscription,
+        Err(reason) => return invalid_subscription_response(reason),
+    };
+    let last_event_id = match headers.get(LAST_EVENT_ID_HEADER) {
+        None => None,
+        Some(value) => match value
+            .to_str()
+            .ok()
+            .and_then(|id| id.trim().parse::<u64>().ok())
+        {
+            Some(id) => Some(id),
+            None => {
+                return invalid_subscription_response(format!(
+                    "Invalid {} header, expected an event id",
+                    LAST_EVENT_ID_HEADER
+                ))
+            }
+        },
+    };
+
+    let (replay, receiver) = match last_event_id {
+        Some(last_event_id) => {
+            let (replay, receiver) = event_bus.subscribe_from(last_event_id);
+            (Some(replay), receiver)
+        }
+        None => (None, event_bus.subscribe()),
+    };
+
+    let mut backlog = Vec::new();
+    if let Some(replay) = replay {
+        if replay.missed > 0 {
+            backlog.push(Err(BroadcastStreamRecvError::Lagged(replay.missed)));
+        }
+        backlog.extend(replay.events.into_iter().map(Ok));
+    }
+
+    // The guard lives as long as the stream, which ends when shutdown starts
+    let guard = in_flight.enter();
+    let stream = tokio_stream::iter(backlog)
+        .chain(BroadcastStream::new(receiver))
+        .filter_map(move |received| {
+            let _ = &guard;
+            match received {
+                Ok(message) if subscription.matches(&message.event) => Some(sse_event(&message)),
+                Ok(_) => None,
+                Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                    warn!(missed, "SSE subscriber lagged behind");
+                    Some(sse_control(&StreamMessage::Lagged { missed }))
+                }
+            }
+        });
+    let stream =
+        futures_util::StreamExt::take_until(stream, in_flight.shutdown_token().cancelled_owned());
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+// Domain events carry their id so the browser reports it back as Last-Event-ID
+fn sse_event(message: &EventMessage) -> Result<Event, Infallible> {
+    Ok(Event::default()
+        .id(message.id.to_string())
+        .json_data(message)
+        .unwrap_or_default())
+}
+
+fn sse_control(message: &StreamMessage) -> Result<Event, Infallible> {
+    Ok(Event::default().json_data(message).unwrap_or_default())
+}
+
+// `_guard` counts the socket as in flight until it closes
+async fn stream_events(
+    mut socket: WebSocket,
+    event_bus: Arc<EventBus>,
+    subscription: Subscription,
+    _guard: InFlightGuard,
+    in_flight: Arc<InFlight>,
+) {
+    let mut subscription = subscription;
+    let shutdown = in_flight.shutdown_token();
+    // Subscribe before acknowledging so nothing published after the ack is missed
+    let mut events = event_bus.subscribe();
+    let subscribed = StreamMessage::Subscribed {
+        tables: subscription.tables(),
+    };
+    if send_json(&mut socket, &subscribed).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                let _ = socket
+                    .send(Message::Close(Some(CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "Server shutting down".into(),
+                    })))
+                    .await;
+                break;
+            }
+
+            received = events.recv() => {
+                let sent = match received {
+                    Ok(message) if subscription.matches(&message.event) => {
+                        send_json(&mut socket, &*message).await
+                    }
+                    Ok(_) => Ok(()),
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!(missed, "Websocket subscriber lagged behind");
+                        send_json(&mut socket, &StreamMessage::Lagged { missed }).await
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if sent.is_err() {
+                    break;
+                }
+            }
+
+            incoming = socket.recv() => {
+                let reply = match incoming {
+                    Some(Ok(Message::Text(text))) => {
+                        match serde_json::from_str::<SubscribeRequest>(&text) {
+                            Ok(request) => {
+                                subscription = Subscription::new(request.tables);
+                                StreamMessage::Subscribed { tables: subscription.tables() }
+                            }
+                            Err(err) => StreamMessage::Error {
+                                msg: format!("Invalid subscription: {}", err),
+                            },
+                        }
+                    }
+                    // Pings are answered by axum, anything else is ignored
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                if send_json(&mut socket, &reply).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    debug!("Websocket subscriber disconnected");
+}
+
+async fn send_json<T: serde::Serialize>(
+    socket: &mut WebSocket,
+    message: &T,
+) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).unwrap_or_else(|_| "".to_string());
+    socket.send(Message::Text(text)).await
+}
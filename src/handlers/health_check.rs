@@ -1,11 +1,28 @@
 use axum::{
+    extract::State,
     http::StatusCode,
     response::{IntoResponse, Response},
+    Json,
 };
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::models::response::GenericResponse;
+use crate::models::response::{ComponentStatus, GenericResponse, ReadinessResponse};
+use crate::utils::database_connection::EXPECTED_SCHEMA_VERSION;
+use crate::AppDatabase;
 
-pub async fn health_checker() -> Response {
+// A dependency that takes longer than this to answer counts as down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Liveness only says the process is up and serving, it never touches the database
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "Server is up", body = GenericResponse))
+)]
+pub async fn liveness_checker() -> Response {
     GenericResponse {
         msg: "I'm healthy!".to_string(),
         status_code: StatusCode::OK.as_u16(),
@@ -13,3 +30,90 @@ pub async fn health_checker() -> Response {
     }
     .into_response()
 }
+
+// Readiness checks every dependency and returns a 503 as soon as one of them is down
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Every component is up", body = ReadinessResponse),
+        (status = 503, description = "A component is down", body = ReadinessResponse)
+    )
+)]
+pub async fn readiness_checker(State(app_database): State<Arc<AppDatabase>>) -> Response {
+    let pool = &app_database.connection_pool;
+
+    let database = check_component("database", async {
+        if !app_database.is_connected() {
+            return Err("Waiting for the initial database connection".to_string());
+        }
+        sqlx::query("SELECT 1")
+            .execute(pool)
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(None)
+    })
+    .await;
+
+    let migrations = check_component("migrations", async {
+        if !app_database.is_connected() {
+            return Err("Waiting for the initial database connection".to_string());
+        }
+        let (version,): (Option<u32>,) =
+            sqlx::query_as("SELECT MAX(version) FROM schema_migrations")
+                .fetch_one(pool)
+                .await
+                .map_err(|err| err.to_string())?;
+        match version {
+            Some(version) if version == EXPECTED_SCHEMA_VERSION => {
+                Ok(Some(format!("At schema version {}", version)))
+            }
+            version => Err(format!(
+                "Expected schema version {} but found {}",
+                EXPECTED_SCHEMA_VERSION,
+                version.map_or("none".to_string(), |version| version.to_string())
+            )),
+        }
+    })
+    .await;
+
+    let components = vec![database, migrations];
+    let ready = components.iter().all(|component| component.status == "up");
+    let status_code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status_code,
+        Json(ReadinessResponse {
+            status: if ready { "ready" } else { "unavailable" }.to_string(),
+            components,
+        }),
+    )
+        .into_response()
+}
+
+async fn check_component<F, E>(name: &str, check: F) -> ComponentStatus
+where
+    F: Future<Output = Result<Option<String>, E>>,
+    E: ToString,
+{
+    let started = Instant::now();
+    let result = tokio::time::timeout(CHECK_TIMEOUT, check).await;
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let (status, detail) = match result {
+        Ok(Ok(detail)) => ("up", detail),
+        Ok(Err(err)) => ("down", Some(err.to_string())),
+        Err(_) => ("down", Some(format!("Timed out after {:?}", CHECK_TIMEOUT))),
+    };
+    ComponentStatus {
+        name: name.to_string(),
+        status: status.to_string(),
+        latency_ms,
+        detail,
+    }
+}
@@ -1,23 +1,64 @@
 use axum::{
     extract::{Path, State},
+    http::header::ETAG,
     response::{IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, Utc};
 use rand::Rng;
+use sqlx::mysql::{MySql, MySqlQueryResult};
 use sqlx::QueryBuilder;
 use std::sync::Arc;
+use tracing::error;
 
 use crate::models::{
-    request::{AddItemsRequest, GetItemRequest, TableItem},
-    response::ItemsResponse,
+    database::Items,
+    request::{AddItemsRequest, GetItemRequest, ItemMatch, SearchItemsRequest, TableItem},
+    response::{DomainEvent, ItemsResponse},
 };
-use crate::utils::response_builder::{ItemErrorResponseBuilder, ItemSuccessResponseBuilder};
+use crate::utils::auth::{Authorized, ReadScope, WriteItemsScope};
+use crate::utils::etag::{item_etag, Conditional, IfMatch};
+use crate::utils::events::EventBus;
+use crate::utils::limits::Limits;
+use crate::utils::pagination::Page;
+use crate::utils::response_builder::{
+    invalid_page_response, invalid_search_response, item_not_found_response,
+    precondition_failed_response, too_many_items_response, ItemErrorResponseBuilder,
+    ItemSuccessResponseBuilder,
+};
+use crate::utils::sales::record_sales;
+use crate::utils::undo_stack::UndoStack;
+use crate::utils::webhooks::enqueue_deliveries;
 use crate::AppDatabase;
 
+#[utoipa::path(
+    post,
+    path = "/items",
+    tag = "items",
+    request_body = GetItemRequest,
+    responses(
+        (status = 200, description = "A page of items for the table", body = ItemsResponse),
+        (status = 400, description = "Invalid limit or cursor", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
 pub async fn get_items(
+    _: Authorized<ReadScope>,
     State(app_database): State<Arc<AppDatabase>>,
+    State(limits): State<Arc<Limits>>,
     Json(body): Json<GetItemRequest>,
 ) -> Response {
+    let page = match Page::new(
+        body.sort,
+        body.direction,
+        body.limit,
+        body.cursor.as_deref(),
+        limits.max_page_size,
+    ) {
+        Ok(page) => page,
+        Err(reason) => return invalid_page_response(reason),
+    };
+
     let mut query = QueryBuilder::new("SELECT * FROM items WHERE table_id = ");
     query.push_bind(body.table_id);
 
@@ -31,119 +72,398 @@ pub async fn get_items(
         query.push(" AND customer_id = ");
         query.push_bind(customer_id.unwrap());
     };
+    push_time_range(&mut query, body.created_after, body.created_before);
+    page.push_to(&mut query);
 
-    match query
-        .push(" ORDER BY created_at DESC")
-        .build_query_as()
-        .fetch_all(&app_database.connection_pool)
-        .await
-    {
-        Ok(rows) => Json(ItemsResponse { items: rows }).into_response(),
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        query.build_query_as().fetch_all(&mut *connection).await
+    }
+    .await;
+    match result {
+        Ok(mut rows) => {
+            let next_cursor = page.next_cursor(&mut rows);
+            Json(ItemsResponse {
+                items: rows,
+                next_cursor,
+            })
+            .into_response()
+        }
 
         Err(err) => {
             let err_resp = err.get_items_err(body);
-            eprintln!("=> {} - {}:\n{}", "get_item", err_resp.msg, err.to_string());
+            error!(handler = "get_item", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/items/search",
+    tag = "items",
+    request_body = SearchItemsRequest,
+    responses(
+        (status = 200, description = "A page of matching items", body = ItemsResponse),
+        (status = 400, description = "Invalid filters, limit or cursor", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn search_items(
+    _: Authorized<ReadScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    State(limits): State<Arc<Limits>>,
+    Json(body): Json<SearchItemsRequest>,
+) -> Response {
+    let page = match Page::new(
+        body.sort,
+        body.direction,
+        body.limit,
+        body.cursor.as_deref(),
+        limits.max_page_size,
+    ) {
+        Ok(page) => page,
+        Err(reason) => return invalid_page_response(reason),
+    };
+    if body.table_ids.as_ref().is_some_and(|ids| ids.is_empty()) {
+        return invalid_search_response("table_ids must not be empty when given".to_string());
+    }
+
+    // Item and customer filters are plain comparisons on the column so idx_item and
+    // idx_customer_id can be used. The column collation makes them case-insensitive.
+    let mut query = QueryBuilder::new("SELECT * FROM items WHERE TRUE");
+    if let Some(item) = &body.item {
+        let (operator, value) = match body.item_match {
+            ItemMatch::Exact => (" = ", item.clone()),
+            ItemMatch::Prefix => (" LIKE ", format!("{}%", escape_like(item))),
+        };
+        let mut push_match = |column: &str| {
+            query.push(format!(" AND {}{}", column, operator));
+            query.push_bind(value.clone());
+            if body.item_match == ItemMatch::Prefix {
+                query.push(" ESCAPE '!'");
+            }
+        };
+        push_match("item");
+        if body.case_sensitive {
+            // The index narrows rows down case-insensitively first, the binary collation filters the rest
+            push_match("item COLLATE utf8mb4_bin");
+        }
+    }
+    if let Some(customer_id) = &body.customer_id {
+        query.push(" AND customer_id = ");
+        query.push_bind(customer_id.clone());
+    }
+    if let Some(table_ids) = &body.table_ids {
+        query.push(" AND table_id IN (");
+        let mut ids = query.separated(", ");
+        for table_id in table_ids {
+            ids.push_bind(*table_id);
+        }
+        ids.push_unseparated(")");
+    }
+    push_time_range(&mut query, body.created_after, body.created_before);
+    page.push_to(&mut query);
+
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        query.build_query_as().fetch_all(&mut *connection).await
+    }
+    .await;
+    match result {
+        Ok(mut rows) => {
+            let next_cursor = page.next_cursor(&mut rows);
+            Json(ItemsResponse {
+                items: rows,
+                next_cursor,
+            })
+            .into_response()
+        }
+
+        Err(err) => {
+            let err_resp = err.search_items_err();
+            error!(handler = "search_items", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+fn push_time_range(
+    query: &mut QueryBuilder<'_, MySql>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+) {
+    if let Some(created_after) = created_after {
+        query.push(" AND created_at > ");
+        query.push_bind(created_after);
+    }
+    if let Some(created_before) = created_before {
+        query.push(" AND created_at < ");
+        query.push_bind(created_before);
+    }
+}
+
+// Wildcards in the search term are matched literally, '!' is used as the LIKE escape character
+fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '!' | '%' | '_') {
+            escaped.push('!');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[utoipa::path(
+    get,
+    path = "/items/{id}",
+    tag = "items",
+    params(("id" = u32, Path, description = "Item id")),
+    responses(
+        (status = 200, description = "The item", body = Items,
+            headers(("etag" = String, description = "Current version of the item"))),
+        (status = 404, description = "Item does not exist", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn get_item(
+    _: Authorized<ReadScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Path(id): Path<u32>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query_as::<_, Items>("SELECT * FROM items WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *connection)
+            .await
+    }
+    .await;
+    match result {
+        Ok(Some(item)) => ([(ETAG, item_etag(&item))], Json(item)).into_response(),
+
+        Ok(None) => item_not_found_response(id),
+
+        Err(err) => {
+            let err_resp = err.get_item_err(id);
+            error!(handler = "get_item", error = %err, "{}", err_resp.msg);
             err_resp.into_response()
         }
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/items/delete/{id}",
+    tag = "items",
+    params(
+        ("id" = u32, Path, description = "Item id"),
+        ("if-match" = Option<String>, Header, description = "Only delete the item at this ETag")
+    ),
+    responses(
+        (status = 200, description = "Item deleted", body = GenericResponse),
+        (status = 412, description = "Item changed since it was read", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
 pub async fn delete_item_by_id(
+    _: Authorized<WriteItemsScope>,
     State(app_database): State<Arc<AppDatabase>>,
+    State(undo_stack): State<Arc<UndoStack>>,
+    State(event_bus): State<Arc<EventBus>>,
     Path(id): Path<u32>,
+    if_match: IfMatch,
 ) -> Response {
-    match sqlx::query!("DELETE FROM items WHERE id = ?", id)
-        .execute(&app_database.connection_pool)
-        .await
-    {
-        Ok(results) => results.delete_item_response(),
+    let mut select = QueryBuilder::new("SELECT * FROM items WHERE id = ");
+    select.push_bind(id);
+
+    match delete_with_snapshot(&app_database, select, if_match).await {
+        Ok(Conditional::Applied((results, deleted))) => {
+            event_bus.publish_all(DomainEvent::items_deleted(&deleted));
+            undo_stack.push_items(deleted);
+            results.delete_item_response()
+        }
+
+        Ok(Conditional::PreconditionFailed(current)) => {
+            precondition_failed_response(format!("Item {}", id), current)
+        }
 
         Err(err) => {
             let err_resp = err.delete_by_id_err(id);
-            eprintln!(
-                "=> {} - {}:\n{}",
-                "delete_item_by_id",
-                err_resp.msg,
-                err.to_string()
-            );
+            error!(handler = "delete_item_by_id", error = %err, "{}", err_resp.msg);
             err_resp.into_response()
         }
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/items/delete",
+    tag = "items",
+    request_body = TableItem,
+    params(("if-match" = Option<String>, Header, description = "Only delete the item at this ETag")),
+    responses(
+        (status = 200, description = "Latest matching item deleted", body = GenericResponse),
+        (status = 412, description = "Item changed since it was read", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
 pub async fn delete_item(
+    _: Authorized<WriteItemsScope>,
     State(app_database): State<Arc<AppDatabase>>,
+    State(undo_stack): State<Arc<UndoStack>>,
+    State(event_bus): State<Arc<EventBus>>,
+    if_match: IfMatch,
     Json(body): Json<TableItem>,
 ) -> Response {
-    let mut query = QueryBuilder::new("DELETE FROM items WHERE table_id = ");
-    query
-        .push_bind(&body.table_id)
+    let mut select = QueryBuilder::new("SELECT * FROM items WHERE table_id = ");
+    select
+        .push_bind(body.table_id)
         .push(" AND item = ")
-        .push_bind(&body.item);
+        .push_bind(body.item.clone());
 
-    let body_clone = body.clone();
-    if body_clone.customer_id.is_some() {
-        query.push(" AND customer_id = ");
-        query.push_bind(body_clone.customer_id.unwrap());
+    if let Some(customer_id) = body.customer_id.clone() {
+        select.push(" AND customer_id = ");
+        select.push_bind(customer_id);
     }
+    select.push(" ORDER BY created_at DESC").push(" LIMIT 1"); // Only delete latest item
+
+    match delete_with_snapshot(&app_database, select, if_match).await {
+        Ok(Conditional::Applied((results, deleted))) => {
+            event_bus.publish_all(DomainEvent::items_deleted(&deleted));
+            undo_stack.push_items(deleted);
+            results.delete_item_response()
+        }
 
-    match query
-        .push(" ORDER BY created_at DESC")
-        .push(" LIMIT 1") // Only delete latest item
-        .build()
-        .execute(&app_database.connection_pool)
-        .await
-    {
-        Ok(results) => results.delete_item_response(),
+        Ok(Conditional::PreconditionFailed(current)) => precondition_failed_response(
+            format!("Latest {} on table {}", body.item, body.table_id),
+            current,
+        ),
 
         Err(err) => {
             let err_resp = err.delete_item_err(body);
-            eprintln!(
-                "=> {} - {}:\n{}",
-                "delete_item",
-                err_resp.msg,
-                err.to_string()
-            );
+            error!(handler = "delete_item", error = %err, "{}", err_resp.msg);
             err_resp.into_response()
         }
     }
 }
 
+// Locks and snapshots the rows matched by `select` before deleting them, so the deletion can be undone.
+// Both callers select at most one row, which is the one If-Match is checked against.
+async fn delete_with_snapshot(
+    app_database: &AppDatabase,
+    mut select: QueryBuilder<'_, MySql>,
+    if_match: IfMatch,
+) -> Result<Conditional<(MySqlQueryResult, Vec<Items>)>, sqlx::Error> {
+    let mut tx = app_database.begin().await?;
+    let deleted: Vec<Items> = select
+        .push(" FOR UPDATE")
+        .build_query_as()
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let current = deleted.first().map(item_etag);
+    if !if_match.matches(current.as_deref()) {
+        return Ok(Conditional::PreconditionFailed(current));
+    }
+    if deleted.is_empty() {
+        // Nothing matched, dropping the transaction rolls it back
+        return Ok(Conditional::Applied((MySqlQueryResult::default(), deleted)));
+    }
+
+    let mut delete = QueryBuilder::new("DELETE FROM items WHERE id IN (");
+    let mut ids = delete.separated(", ");
+    for item in &deleted {
+        ids.push_bind(item.id);
+    }
+    ids.push_unseparated(")");
+
+    let results = delete.build().execute(&mut *tx).await?;
+    record_sales(&mut tx, &deleted).await?;
+    enqueue_deliveries(&mut tx, &DomainEvent::items_deleted(&deleted)).await?;
+    tx.commit().await?;
+    Ok(Conditional::Applied((results, deleted)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/items/add",
+    tag = "items",
+    request_body = AddItemsRequest,
+    responses(
+        (status = 200, description = "Items added", body = GenericResponse),
+        (status = 413, description = "Too many items in one request", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
 pub async fn add_items(
+    _: Authorized<WriteItemsScope>,
     State(app_database): State<Arc<AppDatabase>>,
+    State(limits): State<Arc<Limits>>,
+    State(event_bus): State<Arc<EventBus>>,
     Json(body): Json<AddItemsRequest>,
 ) -> Response {
-    // TODO: Handle bind limit by performing multiple queries
-    // Mysql bind limit for number of fields that we can bind
-    const MYSQL_BIND_LIMIT: usize = 65535 / 4;
-
-    // Using QueryBuilder because sqlx does not support bulk insert by vector
-    match QueryBuilder::new("INSERT INTO items (table_id, item, cook_time, customer_id) ")
-        .push_values(
-            body.to_add.into_iter().take(MYSQL_BIND_LIMIT),
-            |mut builder, item| {
-                builder
-                    .push_bind(item.table_id)
-                    .push_bind(item.item)
-                    .push_bind(rand::thread_rng().gen_range(5..=15)) // Static random time for cook time
-                    .push_bind(item.customer_id);
-            },
-        )
-        .build()
-        .execute(&app_database.connection_pool)
-        .await
-    {
-        Ok(result) => result.add_item_response(),
+    if body.to_add.len() > limits.max_items_per_request {
+        return too_many_items_response(limits.max_items_per_request);
+    }
+
+    match insert_items(&app_database, body.to_add).await {
+        Ok((result, added)) => {
+            event_bus.publish_all(
+                added
+                    .into_iter()
+                    .map(|item| DomainEvent::ItemAdded { item }),
+            );
+            result.add_item_response()
+        }
 
         Err(err) => {
             let err_resp = err.add_items_err();
-            eprintln!(
-                "=> {} - {}:\n{}",
-                "add_items",
-                err_resp.msg,
-                err.to_string()
-            );
+            error!(handler = "add_items", error = %err, "{}", err_resp.msg);
             err_resp.into_response()
         }
     }
 }
+
+// Inserts the items and reads them back with their generated ids and timestamps.
+// Rows go in one at a time so each id comes from its own last_insert_id: a multi-row insert
+// doesn't get consecutive ids with innodb_autoinc_lock_mode=2 or auto_increment_increment > 1.
+// The request size is capped by max_items_per_request.
+async fn insert_items(
+    app_database: &AppDatabase,
+    to_add: Vec<TableItem>,
+) -> Result<(MySqlQueryResult, Vec<Items>), sqlx::Error> {
+    let mut tx = app_database.begin().await?;
+    let mut result = MySqlQueryResult::default();
+    let mut ids = Vec::with_capacity(to_add.len());
+    for item in to_add {
+        // Static random time for cook time
+        let cook_time: u8 = rand::thread_rng().gen_range(5..=15);
+        let inserted = sqlx::query(
+            "INSERT INTO items (table_id, item, cook_time, customer_id) VALUES (?, ?, ?, ?)",
+        )
+        .bind(item.table_id)
+        .bind(item.item)
+        .bind(cook_time)
+        .bind(item.customer_id)
+        .execute(&mut *tx)
+        .await?;
+        ids.push(inserted.last_insert_id());
+        result.extend([inserted]);
+    }
+
+    let mut added = Vec::new();
+    if !ids.is_empty() {
+        let mut select = QueryBuilder::new("SELECT * FROM items WHERE id IN (");
+        let mut separated = select.separated(", ");
+        for id in &ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(") ORDER BY id");
+        added = select.build_query_as().fetch_all(&mut *tx).await?;
+    }
+    enqueue_deliveries(&mut tx, &DomainEvent::items_added(&added)).await?;
+    tx.commit().await?;
+    Ok((result, added))
+}
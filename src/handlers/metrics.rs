@@ -0,0 +1,130 @@
+use axum::{
+    extract::State,
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, warn};
+
+use crate::AppDatabase;
+
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+// Well inside Prometheus' default scrape timeout of 10 seconds
+const BUSINESS_GAUGES_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Request and pool acquire metrics come from the recorder. Pool and business gauges are read at
+// scrape time so they never go stale for tables that have since been deleted, the pool ones
+// without taking a connection.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "metrics",
+    responses((status = 200, description = "Prometheus text format", body = String, content_type = "text/plain"))
+)]
+pub async fn get_metrics(
+    State(app_database): State<Arc<AppDatabase>>,
+    State(metrics_handle): State<PrometheusHandle>,
+) -> Response {
+    let mut body = metrics_handle.render();
+    let pool = &app_database.connection_pool;
+
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+    write_gauge_header(
+        &mut body,
+        "db_pool_connections",
+        "Connections in the database pool by state",
+    );
+    let _ = writeln!(
+        body,
+        "db_pool_connections{{state=\"in_use\"}} {}",
+        size.saturating_sub(idle)
+    );
+    let _ = writeln!(body, "db_pool_connections{{state=\"idle\"}} {}", idle);
+    write_gauge_header(
+        &mut body,
+        "db_pool_max_connections",
+        "Maximum number of connections in the database pool",
+    );
+    let _ = writeln!(
+        body,
+        "db_pool_max_connections {}",
+        pool.options().get_max_connections()
+    );
+
+    // A scrape must answer even when the database is slow or the pool is exhausted, the
+    // business gauges are left out then
+    match tokio::time::timeout(BUSINESS_GAUGES_TIMEOUT, read_business_gauges(&app_database)).await {
+        Ok(Ok(gauges)) => write_business_gauges(&mut body, gauges),
+
+        Ok(Err(err)) => {
+            error!(handler = "get_metrics", error = %err, "Error when attempting to read business metrics");
+        }
+
+        Err(_) => {
+            warn!(
+                handler = "get_metrics",
+                timeout_ms = BUSINESS_GAUGES_TIMEOUT.as_millis() as u64,
+                "Timed out reading business metrics"
+            );
+        }
+    }
+
+    ([(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], body).into_response()
+}
+
+struct BusinessGauges {
+    open_items: Vec<(u32, i64)>,
+    kitchen_queue: i64,
+}
+
+async fn read_business_gauges(app_database: &AppDatabase) -> Result<BusinessGauges, sqlx::Error> {
+    let mut connection = app_database.acquire().await?;
+    let open_items: Vec<(u32, i64)> =
+        sqlx::query_as("SELECT table_id, COUNT(*) FROM items GROUP BY table_id ORDER BY table_id")
+            .fetch_all(&mut *connection)
+            .await?;
+    // An item is still in the kitchen until its cook time has passed
+    let (kitchen_queue,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM items WHERE created_at + INTERVAL cook_time MINUTE > CURRENT_TIMESTAMP",
+    )
+    .fetch_one(&mut *connection)
+    .await?;
+    Ok(BusinessGauges {
+        open_items,
+        kitchen_queue,
+    })
+}
+
+fn write_business_gauges(body: &mut String, gauges: BusinessGauges) {
+    write_gauge_header(
+        body,
+        "restaurant_open_items",
+        "Items currently on each table",
+    );
+    for (table_id, count) in gauges.open_items {
+        let _ = writeln!(
+            body,
+            "restaurant_open_items{{table_id=\"{}\"}} {}",
+            table_id, count
+        );
+    }
+    write_gauge_header(
+        body,
+        "restaurant_kitchen_queue_length",
+        "Items whose cook time has not passed yet",
+    );
+    let _ = writeln!(
+        body,
+        "restaurant_kitchen_queue_length {}",
+        gauges.kitchen_queue
+    );
+}
+
+fn write_gauge_header(body: &mut String, name: &str, help: &str) {
+    let _ = writeln!(body, "# HELP {} {}", name, help);
+    let _ = writeln!(body, "# TYPE {} gauge", name);
+}
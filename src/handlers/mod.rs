@@ -1,3 +1,13 @@
+pub mod api_keys;
+pub mod auth;
+pub mod bulk;
+pub mod docs;
+pub mod events;
 pub mod health_check;
 pub mod items;
+pub mod metrics;
+pub mod reports;
+pub mod staff;
 pub mod tables;
+pub mod undo;
+pub mod webhooks;
@@ -0,0 +1,132 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::request::{BulkFormat, ReportFormat, ReportGroup, ReportQuery};
+use crate::models::response::{ReportResponse, ReportRow};
+use crate::utils::auth::{AdminScope, Authorized};
+use crate::utils::bulk::csv_line;
+use crate::utils::response_builder::{invalid_report_response, ReportErrorResponseBuilder};
+use crate::AppDatabase;
+
+// Reports count the items still open and the ones already served, which are kept in item_sales
+#[utoipa::path(
+    get,
+    path = "/admin/reports/{group_by}",
+    tag = "admin",
+    params(
+        ("group_by" = ReportGroup, Path, description = "dish, hour, table or customer"),
+        ReportQuery
+    ),
+    responses(
+        (status = 200, description = "Items counted per group as JSON, or as CSV with ?format=csv", body = ReportResponse),
+        (status = 400, description = "Empty or inverted time range", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn get_report(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Path(group_by): Path<ReportGroup>,
+    Query(query): Query<ReportQuery>,
+) -> Response {
+    let from = query.from.unwrap_or_else(start_of_today);
+    let to = query.to.unwrap_or(from + Duration::days(1));
+    if to <= from {
+        return invalid_report_response(format!("to {} must be after from {}", to, from));
+    }
+
+    let rows = match report_rows(&app_database, group_by, from, to).await {
+        Ok(rows) => rows,
+        Err(err) => {
+            let err_resp = err.get_report_err(group_by.as_str());
+            error!(handler = "get_report", error = %err, "{}", err_resp.msg);
+            return err_resp.into_response();
+        }
+    };
+
+    match query.format {
+        ReportFormat::Json => Json(ReportResponse {
+            group_by,
+            from,
+            to,
+            total_items: rows.iter().map(|row| row.items).sum(),
+            rows,
+        })
+        .into_response(),
+
+        ReportFormat::Csv => {
+            let mut csv = csv_line(
+                [group_by.as_str(), "items", "revenue", "avg_cook_time"].map(String::from),
+            );
+            for row in rows {
+                csv.push_str(&csv_line([
+                    row.key.unwrap_or_default(),
+                    row.items.to_string(),
+                    row.revenue
+                        .map(|revenue| revenue.to_string())
+                        .unwrap_or_default(),
+                    row.avg_cook_time.to_string(),
+                ]));
+            }
+            (
+                [
+                    (CONTENT_TYPE, BulkFormat::Csv.content_type().to_string()),
+                    (
+                        CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{}-report.csv\"", group_by.as_str()),
+                    ),
+                ],
+                csv,
+            )
+                .into_response()
+        }
+    }
+}
+
+// Busiest dishes and customers come first, hours and tables are in order
+async fn report_rows(
+    app_database: &AppDatabase,
+    group_by: ReportGroup,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<ReportRow>, sqlx::Error> {
+    let (key, order_by) = match group_by {
+        ReportGroup::Dish => ("item", "item_count DESC, item"),
+        ReportGroup::Hour => ("HOUR(created_at)", "HOUR(created_at)"),
+        ReportGroup::Table => ("table_id", "table_id"),
+        ReportGroup::Customer => ("customer_id", "item_count DESC, customer_id"),
+    };
+    // Both expressions come from the match above, never from the request
+    let sql = format!(
+        "SELECT CAST({key} AS CHAR) AS report_key, COUNT(*) AS item_count, CAST(ROUND(AVG(cook_time), 2) AS DOUBLE) AS avg_cook_time FROM (SELECT table_id, item, cook_time, customer_id, created_at FROM items WHERE created_at >= ? AND created_at < ? UNION ALL SELECT table_id, item, cook_time, customer_id, created_at FROM item_sales WHERE created_at >= ? AND created_at < ?) AS ordered GROUP BY {key} ORDER BY {order_by}",
+    );
+    let rows: Vec<(Option<String>, i64, f64)> = sqlx::query_as(&sql)
+        .bind(from)
+        .bind(to)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&mut *app_database.acquire().await?)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(key, items, avg_cook_time)| ReportRow {
+            key,
+            items: items as u64,
+            revenue: None,
+            avg_cook_time,
+        })
+        .collect())
+}
+
+fn start_of_today() -> DateTime<Utc> {
+    let midnight = Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap();
+    Utc.from_utc_datetime(&midnight)
+}
@@ -0,0 +1,155 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::database::Staff;
+use crate::models::request::CreateStaffRequest;
+use crate::models::response::{GenericResponse, StaffResponse};
+use crate::utils::auth::{hash_password, AdminScope, Authorized};
+use crate::utils::response_builder::{
+    invalid_staff_response, StaffErrorResponseBuilder, StaffSuccessResponseBuilder,
+};
+use crate::AppDatabase;
+
+// The width of staff.username
+const MAX_USERNAME_CHARS: usize = 90;
+const MIN_PASSWORD_CHARS: usize = 8;
+
+#[utoipa::path(
+    put,
+    path = "/admin/staff/add",
+    tag = "admin",
+    request_body = CreateStaffRequest,
+    responses(
+        (status = 200, description = "Staff account created", body = GenericResponse),
+        (status = 400, description = "Empty or too long username, or too short password", body = GenericResponse),
+        (status = 500, description = "Username is taken", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn create_staff(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Json(body): Json<CreateStaffRequest>,
+) -> Response {
+    if body.username.trim().is_empty() {
+        return invalid_staff_response("Username is empty".to_string());
+    }
+    if body.username.chars().count() > MAX_USERNAME_CHARS {
+        return invalid_staff_response(format!(
+            "Username is longer than {} characters",
+            MAX_USERNAME_CHARS
+        ));
+    }
+    if body.password.chars().count() < MIN_PASSWORD_CHARS {
+        return invalid_staff_response(format!(
+            "Password is shorter than {} characters",
+            MIN_PASSWORD_CHARS
+        ));
+    }
+
+    // Argon2 is deliberately slow, keep it off the async workers
+    let password = body.password.clone();
+    let password_hash = match tokio::task::spawn_blocking(move || hash_password(&password)).await {
+        Ok(Ok(password_hash)) => password_hash,
+        _ => {
+            let err_resp = GenericResponse {
+                msg: format!(
+                    "Error when attempting to hash password for {}",
+                    body.username
+                ),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                rows: None,
+            };
+            error!(handler = "create_staff", "{}", err_resp.msg);
+            return err_resp.into_response();
+        }
+    };
+
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query("INSERT INTO staff (username, password_hash, role) VALUES (?, ?, ?)")
+            .bind(&body.username)
+            .bind(password_hash)
+            .bind(body.role.as_str())
+            .execute(&mut *connection)
+            .await
+    }
+    .await;
+    match result {
+        Ok(result) => result.create_staff_response(&body.username),
+
+        Err(err) => {
+            let err_resp = err.create_staff_err(&body.username);
+            error!(handler = "create_staff", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/staff",
+    tag = "admin",
+    responses((status = 200, description = "Every staff account", body = StaffResponse)),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn get_staff(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query_as::<_, Staff>("SELECT id, username, role, created_at FROM staff ORDER BY id")
+            .fetch_all(&mut *connection)
+            .await
+    }
+    .await;
+    match result {
+        Ok(staff) => Json(StaffResponse { staff }).into_response(),
+
+        Err(err) => {
+            let err_resp = err.get_staff_err();
+            error!(handler = "get_staff", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+// Outstanding access tokens stay valid until they expire, refreshing them fails straight away
+#[utoipa::path(
+    delete,
+    path = "/admin/staff/delete/{id}",
+    tag = "admin",
+    params(("id" = u32, Path, description = "Staff account id")),
+    responses((status = 200, description = "Staff account deleted", body = GenericResponse)),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn delete_staff(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Path(id): Path<u32>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query("DELETE FROM staff WHERE id = ?")
+            .bind(id)
+            .execute(&mut *connection)
+            .await
+    }
+    .await;
+    match result {
+        Ok(results) => results.delete_staff_response(id),
+
+        Err(err) => {
+            let err_resp = err.delete_staff_err(id);
+            error!(handler = "delete_staff", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
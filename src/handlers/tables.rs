@@ -1,82 +1,198 @@
 use axum::{
     extract::{Path, State},
+    http::header::ETAG,
     response::{IntoResponse, Response},
     Json,
 };
+use sqlx::mysql::MySqlQueryResult;
 use std::sync::Arc;
+use tracing::error;
 
-use crate::models::database::Table;
-use crate::models::response::GetSeatsResponse;
-use crate::utils::response_builder::{TableErrorResponseBuilder, TableSuccessResponseBuilder};
+use crate::models::database::{Items, Table};
+use crate::models::response::{DomainEvent, GetSeatsResponse};
+use crate::utils::auth::{Authorized, ManageTablesScope, ReadScope};
+use crate::utils::etag::{next_table_version, table_etag, Conditional, IfMatch};
+use crate::utils::events::EventBus;
+use crate::utils::response_builder::{
+    precondition_failed_response, TableErrorResponseBuilder, TableSuccessResponseBuilder,
+};
+use crate::utils::sales::record_sales;
+use crate::utils::undo_stack::{UndoEntry, UndoStack};
+use crate::utils::webhooks::enqueue_deliveries;
 use crate::AppDatabase;
 
+#[utoipa::path(
+    get,
+    path = "/table/{id}",
+    tag = "tables",
+    params(("id" = u32, Path, description = "Table id")),
+    responses(
+        (status = 200, description = "Seats at the table", body = GetSeatsResponse,
+            headers(("etag" = String, description = "Current version of the table"))),
+        (status = 500, description = "Table does not exist", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
 pub async fn get_seats(
+    _: Authorized<ReadScope>,
     State(app_database): State<Arc<AppDatabase>>,
     Path(table_id): Path<u32>,
 ) -> Response {
-    match sqlx::query_as!(Table, "SELECT id, seats FROM tables WHERE id = ?", table_id)
-        .fetch_one(&app_database.connection_pool)
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query_as!(
+            Table,
+            "SELECT id, seats, version FROM tables WHERE id = ?",
+            table_id
+        )
+        .fetch_one(&mut *connection)
         .await
-    {
-        Ok(table) => Json(GetSeatsResponse { seats: table.seats }).into_response(),
+    }
+    .await;
+    match result {
+        Ok(table) => (
+            [(ETAG, table_etag(&table))],
+            Json(GetSeatsResponse { seats: table.seats }),
+        )
+            .into_response(),
 
         Err(err) => {
             let err_resp = err.get_seats_err(table_id);
-            eprintln!(
-                "=> {} - {}:\n{}",
-                "get_table",
-                &err_resp.msg,
-                err.to_string()
-            );
+            error!(handler = "get_table", error = %err, "{}", err_resp.msg);
             err_resp.into_response()
         }
     }
 }
 
+#[utoipa::path(
+    put,
+    path = "/table/add",
+    tag = "tables",
+    request_body = Table,
+    responses(
+        (status = 200, description = "Table created", body = GenericResponse),
+        (status = 500, description = "Table already exists", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
 pub async fn add_table(
+    _: Authorized<ManageTablesScope>,
     State(app_database): State<Arc<AppDatabase>>,
+    State(event_bus): State<Arc<EventBus>>,
     Json(body): Json<Table>,
 ) -> Response {
-    match sqlx::query("INSERT INTO tables (id, seats) VALUES (?, ?)")
-        .bind(body.id)
-        .bind(body.seats)
-        .execute(&app_database.connection_pool)
-        .await
-    {
-        Ok(result) => result.add_table_response(body.id, body.seats),
+    match insert_table(&app_database, &body).await {
+        Ok((result, added)) => {
+            event_bus.publish(added);
+            result.add_table_response(body.id, body.seats)
+        }
 
         Err(err) => {
             let err_resp = err.add_table_err(body);
-            eprintln!(
-                "=> {} - {}:\n{}",
-                "add_table",
-                &err_resp.msg,
-                err.to_string()
-            );
+            error!(handler = "add_table", error = %err, "{}", err_resp.msg);
             err_resp.into_response()
         }
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/table/delete/{id}",
+    tag = "tables",
+    params(
+        ("id" = u32, Path, description = "Table id"),
+        ("if-match" = Option<String>, Header, description = "Only delete the table at this ETag")
+    ),
+    responses(
+        (status = 200, description = "Table and its items deleted", body = GenericResponse),
+        (status = 412, description = "Table changed since it was read", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
 pub async fn delete_table_by_id(
+    _: Authorized<ManageTablesScope>,
     State(app_database): State<Arc<AppDatabase>>,
+    State(undo_stack): State<Arc<UndoStack>>,
+    State(event_bus): State<Arc<EventBus>>,
     Path(id): Path<u32>,
+    if_match: IfMatch,
 ) -> Response {
-    match sqlx::query!("DELETE FROM tables WHERE id = ?", id)
-        .execute(&app_database.connection_pool)
-        .await
-    {
-        Ok(results) => results.delete_table_by_id_response(id),
+    match delete_table_with_snapshot(&app_database, id, if_match).await {
+        Ok(Conditional::Applied((results, snapshot))) => {
+            if let Some(entry) = snapshot {
+                event_bus.publish_all(entry.deleted_events());
+                undo_stack.push(id, entry);
+            }
+            results.delete_table_by_id_response(id)
+        }
+
+        Ok(Conditional::PreconditionFailed(current)) => {
+            precondition_failed_response(format!("Table {}", id), current)
+        }
 
         Err(err) => {
             let err_resp = err.delete_table_err(id);
-            eprintln!(
-                "=> {} - {}:\n{}",
-                "delete_table_by_id",
-                &err_resp.msg,
-                err.to_string()
-            );
+            error!(handler = "delete_table_by_id", error = %err, "{}", err_resp.msg);
             err_resp.into_response()
         }
     }
 }
+
+async fn insert_table(
+    app_database: &AppDatabase,
+    table: &Table,
+) -> Result<(MySqlQueryResult, DomainEvent), sqlx::Error> {
+    let mut tx = app_database.begin().await?;
+    let version = next_table_version(&mut tx).await?;
+    let result = sqlx::query("INSERT INTO tables (id, seats, version) VALUES (?, ?, ?)")
+        .bind(table.id)
+        .bind(table.seats)
+        .bind(version)
+        .execute(&mut *tx)
+        .await?;
+    let added = DomainEvent::TableAdded {
+        table: Table {
+            version,
+            ..table.clone()
+        },
+    };
+    enqueue_deliveries(&mut tx, std::slice::from_ref(&added)).await?;
+    tx.commit().await?;
+    Ok((result, added))
+}
+
+// Snapshots the table and its items before the cascading delete so the whole table can be undone.
+// The If-Match check happens under the row lock so a concurrent change can't slip in between.
+async fn delete_table_with_snapshot(
+    app_database: &AppDatabase,
+    id: u32,
+    if_match: IfMatch,
+) -> Result<Conditional<(MySqlQueryResult, Option<UndoEntry>)>, sqlx::Error> {
+    let mut tx = app_database.begin().await?;
+    let table: Option<Table> =
+        sqlx::query_as("SELECT id, seats, version FROM tables WHERE id = ? FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let current = table.as_ref().map(table_etag);
+    if !if_match.matches(current.as_deref()) {
+        // Dropping the transaction rolls it back
+        return Ok(Conditional::PreconditionFailed(current));
+    }
+    let items: Vec<Items> = sqlx::query_as("SELECT * FROM items WHERE table_id = ? FOR UPDATE")
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let results = sqlx::query!("DELETE FROM tables WHERE id = ?", id)
+        .execute(&mut *tx)
+        .await?;
+    record_sales(&mut tx, &items).await?;
+    let snapshot = table.map(|table| UndoEntry::Table { table, items });
+    if let Some(entry) = &snapshot {
+        enqueue_deliveries(&mut tx, &entry.deleted_events()).await?;
+    }
+    tx.commit().await?;
+
+    Ok(Conditional::Applied((results, snapshot)))
+}
@@ -0,0 +1,109 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+};
+use sqlx::QueryBuilder;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::utils::auth::{Authorized, ManageTablesScope};
+use crate::utils::etag::next_table_version;
+use crate::utils::events::EventBus;
+use crate::utils::response_builder::{
+    nothing_to_undo_response, UndoErrorResponseBuilder, UndoSuccessResponseBuilder,
+};
+use crate::utils::sales::cancel_sales;
+use crate::utils::undo_stack::{UndoEntry, UndoStack};
+use crate::utils::webhooks::enqueue_deliveries;
+use crate::AppDatabase;
+
+#[utoipa::path(
+    put,
+    path = "/table/undo/{id}",
+    tag = "tables",
+    params(("id" = u32, Path, description = "Table id")),
+    responses(
+        (status = 200, description = "Last delete restored", body = GenericResponse),
+        (status = 404, description = "Nothing to undo", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn undo_table(
+    _: Authorized<ManageTablesScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    State(undo_stack): State<Arc<UndoStack>>,
+    State(event_bus): State<Arc<EventBus>>,
+    Path(table_id): Path<u32>,
+) -> Response {
+    let record = match undo_stack.pop(table_id) {
+        Some(record) => record,
+        None => return nothing_to_undo_response(table_id),
+    };
+
+    let mut restored = record.entry.restored();
+    match restore_entry(&app_database, &mut restored).await {
+        Ok(rows) => {
+            event_bus.publish_all(restored.added_events());
+            record.entry.undo_response(table_id, rows)
+        }
+
+        Err(err) => {
+            // Put the entry back so the client can retry once the conflict is resolved,
+            // still expiring when the original delete does
+            undo_stack.push_back(table_id, record);
+            let err_resp = err.undo_err(table_id);
+            error!(handler = "undo_table", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+// Re-inserts the snapshot with its original ids and timestamps. A restored table gets the
+// next version from the sequence, which is written back into the entry.
+async fn restore_entry(
+    app_database: &AppDatabase,
+    entry: &mut UndoEntry,
+) -> Result<u64, sqlx::Error> {
+    let mut tx = app_database.begin().await?;
+    let mut rows = 0;
+
+    let items = match entry {
+        UndoEntry::Items(items) => items,
+        UndoEntry::Table { table, items } => {
+            table.version = next_table_version(&mut tx).await?;
+            rows += sqlx::query("INSERT INTO tables (id, seats, version) VALUES (?, ?, ?)")
+                .bind(table.id)
+                .bind(table.seats)
+                .bind(table.version)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            items
+        }
+    };
+
+    if !items.is_empty() {
+        rows += QueryBuilder::new(
+            "INSERT INTO items (id, table_id, item, cook_time, customer_id, created_at, version) ",
+        )
+        .push_values(items.iter(), |mut builder, item| {
+            builder
+                .push_bind(item.id)
+                .push_bind(item.table_id)
+                .push_bind(&item.item)
+                .push_bind(item.cook_time)
+                .push_bind(&item.customer_id)
+                .push_bind(item.created_at)
+                .push_bind(item.version);
+        })
+        .build()
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        cancel_sales(&mut tx, items).await?;
+    }
+
+    enqueue_deliveries(&mut tx, &entry.added_events()).await?;
+    tx.commit().await?;
+    Ok(rows)
+}
@@ -0,0 +1,220 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::database::{WebhookDelivery, WebhookSubscription};
+use crate::models::request::CreateWebhookRequest;
+use crate::models::response::{
+    CreateWebhookResponse, DeadLettersResponse, DomainEvent, WebhooksResponse,
+};
+use crate::utils::auth::{random_hex, AdminScope, Authorized};
+use crate::utils::response_builder::{
+    invalid_webhook_response, WebhookErrorResponseBuilder, WebhookSuccessResponseBuilder,
+};
+use crate::utils::webhooks::Webhooks;
+use crate::AppDatabase;
+
+#[utoipa::path(
+    put,
+    path = "/admin/webhooks/add",
+    tag = "admin",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 200, description = "Subscription created, the secret is only returned here", body = CreateWebhookResponse),
+        (status = 400, description = "Invalid url or event type", body = GenericResponse)
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn create_webhook(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Json(body): Json<CreateWebhookRequest>,
+) -> Response {
+    if !(body.url.starts_with("http://") || body.url.starts_with("https://"))
+        || reqwest::Url::parse(&body.url).is_err()
+    {
+        return invalid_webhook_response(format!("Invalid webhook url {}", body.url));
+    }
+    let events = body.events.unwrap_or_default();
+    if let Some(unknown) = events
+        .iter()
+        .find(|event| !DomainEvent::TYPES.contains(&event.as_str()))
+    {
+        return invalid_webhook_response(format!(
+            "Unknown event type {}, expected one of {}",
+            unknown,
+            DomainEvent::TYPES.join(", ")
+        ));
+    }
+
+    // Secrets are stored as given since every delivery is signed with them
+    let secret = body
+        .secret
+        .filter(|secret| !secret.is_empty())
+        .unwrap_or_else(|| format!("whsec_{}", random_hex(24)));
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query("INSERT INTO webhook_subscriptions (url, secret, events) VALUES (?, ?, ?)")
+            .bind(&body.url)
+            .bind(&secret)
+            .bind(events.join(","))
+            .execute(&mut *connection)
+            .await
+    }
+    .await;
+    match result {
+        Ok(result) => Json(CreateWebhookResponse {
+            id: result.last_insert_id(),
+            url: body.url,
+            events,
+            secret,
+        })
+        .into_response(),
+
+        Err(err) => {
+            let err_resp = err.create_webhook_err(&body.url);
+            error!(handler = "create_webhook", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks",
+    tag = "admin",
+    responses((status = 200, description = "Every webhook subscription", body = WebhooksResponse)),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn get_webhooks(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query_as::<_, WebhookSubscription>(
+            "SELECT id, url, events, created_at FROM webhook_subscriptions ORDER BY id",
+        )
+        .fetch_all(&mut *connection)
+        .await
+    }
+    .await;
+    match result {
+        Ok(webhooks) => Json(WebhooksResponse { webhooks }).into_response(),
+
+        Err(err) => {
+            let err_resp = err.get_webhooks_err();
+            error!(handler = "get_webhooks", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+// Pending and dead deliveries for the subscription are removed with it
+#[utoipa::path(
+    delete,
+    path = "/admin/webhooks/delete/{id}",
+    tag = "admin",
+    params(("id" = u32, Path, description = "Subscription id")),
+    responses((status = 200, description = "Subscription deleted", body = GenericResponse)),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn delete_webhook(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    Path(id): Path<u32>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query("DELETE FROM webhook_subscriptions WHERE id = ?")
+            .bind(id)
+            .execute(&mut *connection)
+            .await
+    }
+    .await;
+    match result {
+        Ok(results) => results.delete_webhook_response(id),
+
+        Err(err) => {
+            let err_resp = err.delete_webhook_err(id);
+            error!(handler = "delete_webhook", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/dead",
+    tag = "admin",
+    responses((status = 200, description = "Deliveries that ran out of attempts", body = DeadLettersResponse)),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn get_dead_letters(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query_as::<_, WebhookDelivery>(
+            "SELECT id, subscription_id, event_type, payload, status, attempts, last_status_code, \
+             last_error, created_at FROM webhook_outbox WHERE status = 'dead' ORDER BY id",
+        )
+        .fetch_all(&mut *connection)
+        .await
+    }
+    .await;
+    match result {
+        Ok(deliveries) => Json(DeadLettersResponse { deliveries }).into_response(),
+
+        Err(err) => {
+            let err_resp = err.get_dead_letters_err();
+            error!(handler = "get_dead_letters", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+// Moves a dead delivery back into the outbox with a fresh set of attempts
+#[utoipa::path(
+    put,
+    path = "/admin/webhooks/dead/retry/{id}",
+    tag = "admin",
+    params(("id" = u64, Path, description = "Delivery id")),
+    responses((status = 200, description = "Delivery queued again", body = GenericResponse)),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+pub async fn retry_dead_letter(
+    _: Authorized<AdminScope>,
+    State(app_database): State<Arc<AppDatabase>>,
+    State(webhooks): State<Arc<Webhooks>>,
+    Path(id): Path<u64>,
+) -> Response {
+    let result = async {
+        let mut connection = app_database.acquire().await?;
+        sqlx::query(
+            "UPDATE webhook_outbox SET status = 'pending', attempts = 0, \
+             next_attempt_at = CURRENT_TIMESTAMP WHERE id = ? AND status = 'dead'",
+        )
+        .bind(id)
+        .execute(&mut *connection)
+        .await
+    }
+    .await;
+    match result {
+        Ok(results) => {
+            webhooks.wake();
+            results.retry_delivery_response(id)
+        }
+
+        Err(err) => {
+            let err_resp = err.retry_delivery_err(id);
+            error!(handler = "retry_dead_letter", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use axum::{
+    extract::DefaultBodyLimit,
+    handler::Handler,
+    http::Method,
+    middleware,
+    routing::{on, MethodFilter, MethodRouter},
+    Router,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceBuilder;
+use tower_http::trace::TraceLayer;
+
+pub mod handlers;
+pub mod models;
+pub mod utils;
+use handlers::api_keys::{create_api_key, get_api_keys, revoke_api_key};
+use handlers::auth::{login, logout, refresh};
+use handlers::bulk::{export_items, export_tables, import_items, import_tables};
+use handlers::docs::{get_docs, get_docs_asset, get_openapi};
+use handlers::events::{events_sse, events_ws};
+use handlers::health_check::{liveness_checker, readiness_checker};
+use handlers::items::{
+    add_items, delete_item, delete_item_by_id, get_item, get_items, search_items,
+};
+use handlers::metrics::get_metrics;
+use handlers::reports::get_report;
+use handlers::staff::{create_staff, delete_staff, get_staff};
+use handlers::tables::{add_table, delete_table_by_id, get_seats};
+use handlers::undo::undo_table;
+use handlers::webhooks::{
+    create_webhook, delete_webhook, get_dead_letters, get_webhooks, retry_dead_letter,
+};
+use utils::app_state::AppState;
+use utils::auth::authenticate;
+use utils::config::Config;
+use utils::database_connection::AppDatabase;
+use utils::idempotency::{idempotency, IdempotencyStore};
+use utils::limits::{payload_too_large_envelope, rate_limit};
+use utils::logging::{
+    log_response, make_request_span, propagate_request_id_layer, set_request_id_layer,
+};
+use utils::metrics::track_metrics;
+use utils::shutdown::track_in_flight;
+
+// Builds the API with every route and middleware, ready to serve or to drive in-process with
+// tower's ServiceExt::oneshot. Requests are counted in `app_state.in_flight` so the caller can
+// drain them.
+// Rate limiting reads the client address, so serve it with
+// `into_make_service_with_connect_info::<SocketAddr>()` or turn `features.rate_limiting` off.
+pub fn build_router(app_state: AppState, config: &Config) -> Router {
+    // Everything other than health, metrics, docs and login needs credentials, the scopes each
+    // route needs are checked by the Authorized extractor on its handler.
+    let mut protected_routes = Router::new();
+    for route in protected_routes_table(config) {
+        protected_routes = protected_routes.route(route.path, route.handler);
+    }
+    // Idempotency runs after authentication so stored responses are scoped to the caller
+    let idempotency_store = Arc::new(IdempotencyStore::new(
+        Duration::from_secs(config.idempotency.ttl_secs),
+        config.limits.max_body_bytes,
+    ));
+    let protected_routes = protected_routes
+        .route_layer(middleware::from_fn_with_state(
+            idempotency_store,
+            idempotency,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            authenticate,
+        ));
+
+    let mut app = Router::new();
+    for route in public_routes_table(config) {
+        app = app.route(route.path, route.handler);
+    }
+    let mut app = app.merge(protected_routes);
+
+    // Oversized bodies are rejected with a 413 before any handler runs
+    app = app
+        .layer(DefaultBodyLimit::max(app_state.limits.max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            app_state.limits.clone(),
+            payload_too_large_envelope,
+        ));
+    if config.features.rate_limiting {
+        app = app.layer(middleware::from_fn_with_state(
+            app_state.limits.clone(),
+            rate_limit,
+        ));
+    }
+    // Outside the limits so rejected requests are counted too
+    if config.features.metrics {
+        app = app.layer(middleware::from_fn(track_metrics));
+    }
+    app.layer(middleware::from_fn_with_state(
+        app_state.in_flight.clone(),
+        track_in_flight,
+    ))
+    // Outermost so every response, rejections included, is traced and carries a request id
+    .layer(
+        ServiceBuilder::new()
+            .layer(set_request_id_layer())
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(make_request_span)
+                    .on_request(())
+                    .on_response(log_response),
+            )
+            .layer(propagate_request_id_layer()),
+    )
+    .with_state(app_state)
+}
+
+// A route with the one method it answers to
+struct ApiRoute {
+    method: Method,
+    path: &'static str,
+    handler: MethodRouter<AppState>,
+}
+
+fn route<H, T>(method: Method, path: &'static str, handler: H) -> ApiRoute
+where
+    H: Handler<T, AppState>,
+    T: 'static,
+{
+    let filter = MethodFilter::try_from(method.clone()).expect("Routes use standard methods");
+    ApiRoute {
+        method,
+        path,
+        handler: on(filter, handler),
+    }
+}
+
+// Every route build_router serves with this config, as (method, path). The docs test compares
+// it with the paths in the OpenAPI document.
+pub fn route_table(config: &Config) -> Vec<(Method, &'static str)> {
+    public_routes_table(config)
+        .into_iter()
+        .chain(protected_routes_table(config))
+        .map(|route| (route.method, route.path))
+        .collect()
+}
+
+fn public_routes_table(config: &Config) -> Vec<ApiRoute> {
+    let mut routes = vec![
+        route(Method::GET, "/health/live", liveness_checker),
+        route(Method::GET, "/health/ready", readiness_checker),
+        route(Method::POST, "/auth/login", login),
+        route(Method::POST, "/auth/refresh", refresh),
+    ];
+    if config.features.metrics {
+        routes.push(route(Method::GET, "/metrics", get_metrics));
+    }
+    if config.features.docs {
+        routes.push(route(Method::GET, "/openapi.json", get_openapi));
+        routes.push(route(Method::GET, "/docs", get_docs));
+        routes.push(route(Method::GET, "/docs/:asset", get_docs_asset));
+    }
+    routes
+}
+
+fn protected_routes_table(config: &Config) -> Vec<ApiRoute> {
+    let mut routes = vec![
+        route(Method::GET, "/table/:id", get_seats),
+        route(Method::PUT, "/table/add", add_table),
+        route(Method::DELETE, "/table/delete/:id", delete_table_by_id),
+        route(Method::PUT, "/table/undo/:id", undo_table),
+        route(Method::POST, "/items", get_items),
+        route(Method::POST, "/items/search", search_items),
+        route(Method::GET, "/items/:id", get_item),
+        route(Method::PUT, "/items/add", add_items),
+        route(Method::DELETE, "/items/delete", delete_item),
+        route(Method::DELETE, "/items/delete/:id", delete_item_by_id),
+        route(Method::POST, "/auth/logout", logout),
+        route(Method::GET, "/admin/keys", get_api_keys),
+        route(Method::PUT, "/admin/keys/add", create_api_key),
+        route(Method::DELETE, "/admin/keys/delete/:id", revoke_api_key),
+        route(Method::GET, "/admin/staff", get_staff),
+        route(Method::PUT, "/admin/staff/add", create_staff),
+        route(Method::DELETE, "/admin/staff/delete/:id", delete_staff),
+        route(Method::GET, "/admin/webhooks", get_webhooks),
+        route(Method::PUT, "/admin/webhooks/add", create_webhook),
+        route(Method::DELETE, "/admin/webhooks/delete/:id", delete_webhook),
+        route(Method::GET, "/admin/webhooks/dead", get_dead_letters),
+        route(
+            Method::PUT,
+            "/admin/webhooks/dead/retry/:id",
+            retry_dead_letter,
+        ),
+        route(Method::GET, "/admin/export/tables", export_tables),
+        route(Method::GET, "/admin/export/items", export_items),
+        route(Method::PUT, "/admin/import/tables", import_tables),
+        route(Method::PUT, "/admin/import/items", import_items),
+        route(Method::GET, "/admin/reports/:group_by", get_report),
+    ];
+    if config.features.websockets {
+        routes.push(route(Method::GET, "/events/ws", events_ws));
+    }
+    if config.features.sse {
+        routes.push(route(Method::GET, "/events/sse", events_sse));
+    }
+    routes
+}
@@ -1,49 +1,118 @@
-use axum::{
-    routing::{delete, get, post, put},
-    Router,
-};
+use clap::Parser;
 use dotenv::dotenv;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{error, info};
 
-mod handlers;
-mod models;
-mod utils;
-use handlers::health_check::health_checker;
-use handlers::items::{add_items, delete_item, delete_item_by_id, get_items};
-use handlers::tables::{add_table, delete_table_by_id, get_seats};
-use utils::database_connection::{database_connect, AppDatabase};
+use restaurant_api::build_router;
+use restaurant_api::utils::app_state::AppState;
+use restaurant_api::utils::config::{Cli, Config};
+use restaurant_api::utils::database_connection::{
+    database_connect, wait_for_database, AppDatabase,
+};
+use restaurant_api::utils::logging::init_logging;
+use restaurant_api::utils::metrics::install_metrics_recorder;
+use restaurant_api::utils::shutdown::{drain, shutdown_signal};
 
 #[tokio::main]
 async fn main() {
-    // Load env vars from .env
+    // Load env vars from .env, then resolve the config file, env vars and flags into one config
     dotenv().ok();
-    // Establish a pool of db connections
-    let app_database: AppDatabase = match database_connect().await {
+    let cli = Cli::parse();
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(err) => {
+            // Logging is configured by the config, so this one goes straight to stderr
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    if cli.print_config {
+        print!("{}", config.to_redacted_toml());
+        return;
+    }
+
+    init_logging(&config.logging);
+    // Set up the pool of db connections, connecting happens in the background below
+    let app_database: AppDatabase = match database_connect(&config.database) {
         Ok(app_database) => app_database,
         Err(err) => {
-            eprintln!("Failed to connect to database: {:?}", err);
+            error!(error = %err, "Invalid database configuration");
             std::process::exit(1);
         }
     };
 
-    // Register api routes
-    let app = Router::new()
-        .route("/health", get(health_checker))
-        .route("/table/:id", get(get_seats))
-        .route("/table/add", put(add_table))
-        .route("/table/delete/:id", delete(delete_table_by_id))
-        .route("/items", post(get_items))
-        .route("/items/add", put(add_items))
-        .route("/items/delete", delete(delete_item))
-        .route("/items/delete/:id", delete(delete_item_by_id))
-        .with_state(Arc::new(app_database));
+    let app_state = AppState::new(&config, app_database, install_metrics_recorder());
+
+    // Serve straight away, /health/ready reports 503 until the database is reachable
+    let startup_database = app_state.app_database.clone();
+    let database_config = config.database.clone();
+    tokio::spawn(async move {
+        if let Err(err) = wait_for_database(&startup_database, &database_config).await {
+            error!(error = %err, "Gave up waiting for the database");
+            std::process::exit(1);
+        }
+    });
+
+    // Outbox deliveries are picked up from the database, so subscriptions can still be
+    // managed while delivery is turned off
+    if config.features.webhooks {
+        app_state
+            .webhooks
+            .clone()
+            .spawn(app_state.app_database.clone(), &app_state.event_bus);
+    }
+
+    let in_flight = app_state.in_flight.clone();
+    let app_database = app_state.app_database.clone();
+
+    let app = build_router(app_state, &config);
 
     // Build server address
-    let app_host = std::env::var("APP_HOST").expect("APP_HOST env var not set!");
-    let app_port = std::env::var("APP_PORT").expect("APP_PORT env var not set!");
-    let addr = format!("{}:{}", app_host, app_port);
+    let addr = format!("{}:{}", config.server.host, config.server.port);
     // Start TCP listener
     let tcp_listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    println!("Listening on {}", addr);
-    axum::serve(tcp_listener, app).await.unwrap() // this is our server!
+    info!("Listening on {}", addr);
+    // Client addresses are needed for per-IP rate limiting.
+    // On shutdown the listener stops accepting and in-flight requests get the drain timeout to finish.
+    // Event streams would never finish on their own, so they are told to end.
+    let shutdown_started = Arc::new(Notify::new());
+    let server = axum::serve(
+        tcp_listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown({
+        let shutdown_started = shutdown_started.clone();
+        let in_flight = in_flight.clone();
+        async move {
+            shutdown_signal().await;
+            in_flight.start_shutdown();
+            shutdown_started.notify_one();
+        }
+    });
+    let mut server = tokio::spawn(async move { server.await }); // this is our server!
+
+    tokio::select! {
+        result = &mut server => {
+            // Only reachable if the server stopped without a shutdown signal
+            result.unwrap().unwrap();
+            return;
+        }
+        _ = shutdown_started.notified() => {}
+    }
+
+    let drain_timeout = Duration::from_secs(config.server.shutdown_drain_timeout_secs);
+    let summary = drain(&mut server, &in_flight, drain_timeout).await;
+
+    app_database.connection_pool.close().await;
+    info!(
+        drained = summary.drained,
+        in_flight_at_signal = summary.in_flight_at_signal,
+        abandoned = summary.abandoned,
+        requests_served = summary.requests_served,
+        drain_ms = summary.drain_ms,
+        "Shutdown complete"
+    );
 }
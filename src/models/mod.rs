@@ -1,3 +1,2 @@
-pub mod database;
-pub mod request;
-pub mod response;
+// The models live in their own crate so the client can share them with the server
+pub use restaurant_api_models::{database, request, response};
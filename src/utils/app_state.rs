@@ -0,0 +1,103 @@
+use axum::extract::FromRef;
+use metrics_exporter_prometheus::PrometheusHandle;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::utils::auth::{dummy_password_hash, ApiKeyAuth};
+use crate::utils::config::Config;
+use crate::utils::database_connection::AppDatabase;
+use crate::utils::events::EventBus;
+use crate::utils::jwt::JwtKeys;
+use crate::utils::limits::Limits;
+use crate::utils::shutdown::InFlight;
+use crate::utils::undo_stack::UndoStack;
+use crate::utils::webhooks::Webhooks;
+
+// Shared state handed to the router. Handlers extract only the pieces they need through FromRef,
+// so existing handlers can keep taking State<Arc<AppDatabase>>
+#[derive(Clone)]
+pub struct AppState {
+    pub app_database: Arc<AppDatabase>,
+    pub undo_stack: Arc<UndoStack>,
+    pub api_key_auth: Arc<ApiKeyAuth>,
+    pub jwt_keys: Arc<JwtKeys>,
+    pub limits: Arc<Limits>,
+    pub event_bus: Arc<EventBus>,
+    pub webhooks: Arc<Webhooks>,
+    pub in_flight: Arc<InFlight>,
+    pub metrics_handle: PrometheusHandle,
+}
+
+impl AppState {
+    // The metrics handle is passed in since the global recorder can only be installed once
+    pub fn new(
+        config: &Config,
+        app_database: AppDatabase,
+        metrics_handle: PrometheusHandle,
+    ) -> AppState {
+        // Hashed up front so the first login for an unknown username isn't the slow one
+        dummy_password_hash();
+        AppState {
+            app_database: Arc::new(app_database),
+            undo_stack: Arc::new(UndoStack::new(Duration::from_secs(config.undo.window_secs))),
+            api_key_auth: Arc::new(ApiKeyAuth::new(config.auth.admin_api_key.as_deref())),
+            jwt_keys: Arc::new(JwtKeys::new(&config.auth)),
+            limits: Arc::new(Limits::new(&config.limits)),
+            event_bus: Arc::new(EventBus::new(
+                config.events.channel_capacity,
+                config.events.replay_buffer_size,
+            )),
+            webhooks: Arc::new(Webhooks::new(config.webhooks.clone())),
+            in_flight: Arc::new(InFlight::default()),
+            metrics_handle,
+        }
+    }
+}
+
+impl FromRef<AppState> for Arc<AppDatabase> {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.app_database.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<UndoStack> {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.undo_stack.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<JwtKeys> {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.jwt_keys.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Limits> {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.limits.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<EventBus> {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.event_bus.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Webhooks> {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.webhooks.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<InFlight> {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.in_flight.clone()
+    }
+}
+
+impl FromRef<AppState> for PrometheusHandle {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.metrics_handle.clone()
+    }
+}
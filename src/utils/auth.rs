@@ -0,0 +1,237 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header::AUTHORIZATION, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+use tracing::error;
+
+use crate::models::database::Scope;
+use crate::utils::app_state::AppState;
+use crate::utils::jwt::{is_revoked, Claims, TokenType};
+use crate::utils::response_builder::{
+    forbidden_response, unauthorized_response, AuthErrorResponseBuilder,
+};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+// Holds the hash of the bootstrap admin key configured through ADMIN_API_KEY.
+// It is needed to create the first keys, and is never written to the database.
+pub struct ApiKeyAuth {
+    admin_key_hash: Option<String>,
+}
+
+impl ApiKeyAuth {
+    pub fn new(admin_api_key: Option<&str>) -> Self {
+        ApiKeyAuth {
+            admin_key_hash: admin_api_key
+                .filter(|key| !key.is_empty())
+                .map(hash_api_key),
+        }
+    }
+}
+
+pub fn random_hex(num_bytes: usize) -> String {
+    let mut bytes = vec![0u8; num_bytes];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Keys are random 256 bit values, so a plain SHA-256 is enough to store them safely
+pub fn hash_api_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn generate_api_key() -> String {
+    format!("rk_{}", random_hex(32))
+}
+
+pub fn scopes_to_string(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.as_str())
+        .collect::<Vec<&str>>()
+        .join(",")
+}
+
+pub fn scopes_from_string(scopes: &str) -> Vec<Scope> {
+    scopes.split(',').filter_map(Scope::parse).collect()
+}
+
+// Passwords are low entropy, so unlike API keys they get a slow salted hash
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt)?;
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+// Logins for unknown usernames are checked against this hash, so they take as long as a wrong
+// password and response times don't reveal which usernames exist
+pub fn dummy_password_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        hash_password(&random_hex(16)).expect("Hashing a random password can't fail")
+    })
+}
+
+// Who is making the request, resolved once by the authenticate middleware
+#[derive(Debug, Clone)]
+pub enum Principal {
+    ApiKey { scopes: Vec<Scope> },
+    Staff(Claims),
+}
+
+impl Principal {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        let scopes = match self {
+            Principal::ApiKey { scopes } => scopes.clone(),
+            Principal::Staff(claims) => claims.role.scopes(),
+        };
+        scopes.contains(&scope) || scopes.contains(&Scope::Admin)
+    }
+}
+
+// Resolves the caller from either the x-api-key header or a staff Bearer token and rejects
+// the request with a 401 when neither is valid. Scopes are checked by the Authorized extractor.
+pub async fn authenticate(
+    State(app_state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    let principal = if let Some(key) = headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        key_principal(&app_state, key).await
+    } else if let Some(token) = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        token_principal(&app_state, token).await
+    } else {
+        Ok(None)
+    };
+
+    match principal {
+        Ok(Some(principal)) => {
+            request.extensions_mut().insert(principal);
+            next.run(request).await
+        }
+        Ok(None) => unauthorized_response(),
+
+        Err(err) => {
+            let err_resp = err.authenticate_err();
+            error!(handler = "authenticate", error = %err, "{}", err_resp.msg);
+            err_resp.into_response()
+        }
+    }
+}
+
+// None when the key is unknown or revoked
+async fn key_principal(app_state: &AppState, key: &str) -> Result<Option<Principal>, sqlx::Error> {
+    let key_hash = hash_api_key(key);
+    if app_state.api_key_auth.admin_key_hash.as_ref() == Some(&key_hash) {
+        return Ok(Some(Principal::ApiKey {
+            scopes: vec![Scope::Admin],
+        }));
+    }
+
+    let mut connection = app_state.app_database.acquire().await?;
+    let scopes: Option<(String,)> =
+        sqlx::query_as("SELECT scopes FROM api_keys WHERE key_hash = ? AND revoked_at IS NULL")
+            .bind(key_hash)
+            .fetch_optional(&mut *connection)
+            .await?;
+
+    Ok(scopes.map(|(scopes,)| Principal::ApiKey {
+        scopes: scopes_from_string(&scopes),
+    }))
+}
+
+// None when the token is invalid, expired, not an access token or has been logged out
+async fn token_principal(
+    app_state: &AppState,
+    token: &str,
+) -> Result<Option<Principal>, sqlx::Error> {
+    let claims = match app_state.jwt_keys.verify(token, TokenType::Access) {
+        Some(claims) => claims,
+        None => return Ok(None),
+    };
+    let mut connection = app_state.app_database.acquire().await?;
+    if is_revoked(&mut connection, &claims.jti).await? {
+        return Ok(None);
+    }
+    Ok(Some(Principal::Staff(claims)))
+}
+
+// Marker types naming the scope a handler needs, used as Authorized<ReadScope> etc.
+pub trait RequiredScope {
+    const SCOPE: Scope;
+}
+
+pub struct ReadScope;
+pub struct WriteItemsScope;
+pub struct ManageTablesScope;
+pub struct AdminScope;
+
+impl RequiredScope for ReadScope {
+    const SCOPE: Scope = Scope::Read;
+}
+
+impl RequiredScope for WriteItemsScope {
+    const SCOPE: Scope = Scope::WriteItems;
+}
+
+impl RequiredScope for ManageTablesScope {
+    const SCOPE: Scope = Scope::ManageTables;
+}
+
+impl RequiredScope for AdminScope {
+    const SCOPE: Scope = Scope::Admin;
+}
+
+// Extractor that only lets a request through when the authenticated caller holds scope S
+pub struct Authorized<S: RequiredScope>(PhantomData<S>);
+
+#[async_trait]
+impl<S, St> FromRequestParts<St> for Authorized<S>
+where
+    S: RequiredScope,
+    St: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &St) -> Result<Self, Self::Rejection> {
+        match parts.extensions.get::<Principal>() {
+            Some(principal) if principal.has_scope(S::SCOPE) => Ok(Authorized(PhantomData)),
+            Some(_) => Err(forbidden_response(S::SCOPE)),
+            // Route was registered without the authenticate middleware
+            None => Err(unauthorized_response()),
+        }
+    }
+}
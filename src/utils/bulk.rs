@@ -0,0 +1,538 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::{MySqlConnection, MySqlPool, MySqlRow};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+
+use crate::models::database::{Items, Table};
+use crate::models::request::{BulkFormat, ImportMode};
+use crate::models::response::{DomainEvent, ImportError, ImportReport};
+use crate::utils::database_connection::{acquire_from, AppDatabase};
+use crate::utils::etag::next_table_version;
+use crate::utils::webhooks::enqueue_deliveries;
+
+// Columns of an export, in order. Imports only need the ones they write, the rest are ignored so
+// an export can be imported as is.
+const TABLE_COLUMNS: [&str; 3] = ["id", "seats", "version"];
+const ITEM_COLUMNS: [&str; 7] = [
+    "id",
+    "table_id",
+    "item",
+    "cook_time",
+    "customer_id",
+    "created_at",
+    "version",
+];
+// Length of the VARCHAR columns
+const MAX_TEXT_CHARS: usize = 90;
+// Rows read ahead of a slow consumer
+const EXPORT_BUFFER_ROWS: usize = 64;
+// Bounds the report, `failed` still counts every error
+const MAX_REPORTED_ERRORS: usize = 100;
+
+// Lines of an export, each ending in a newline. A database error ends the stream.
+pub type ExportStream = ReceiverStream<Result<String, sqlx::Error>>;
+
+pub fn export_tables(pool: MySqlPool, format: BulkFormat) -> ExportStream {
+    export(
+        pool,
+        "SELECT id, seats, version FROM tables ORDER BY id",
+        format,
+        &TABLE_COLUMNS,
+        |table: &Table| {
+            vec![
+                table.id.to_string(),
+                table.seats.to_string(),
+                table.version.to_string(),
+            ]
+        },
+    )
+}
+
+pub fn export_items(pool: MySqlPool, format: BulkFormat) -> ExportStream {
+    export(
+        pool,
+        "SELECT * FROM items ORDER BY id",
+        format,
+        &ITEM_COLUMNS,
+        |item: &Items| {
+            vec![
+                item.id.to_string(),
+                item.table_id.to_string(),
+                item.item.clone(),
+                item.cook_time.to_string(),
+                item.customer_id.clone().unwrap_or_default(),
+                item.created_at.to_rfc3339(),
+                item.version.to_string(),
+            ]
+        },
+    )
+}
+
+// Rows are read with a single query in a background task and handed over as they arrive, so an
+// export never holds the whole table in memory. Dropping the stream stops the query.
+fn export<T>(
+    pool: MySqlPool,
+    sql: &'static str,
+    format: BulkFormat,
+    columns: &'static [&'static str],
+    csv_fields: fn(&T) -> Vec<String>,
+) -> ExportStream
+where
+    T: for<'r> sqlx::FromRow<'r, MySqlRow> + Serialize + Send + Unpin + 'static,
+{
+    let (sender, receiver) = mpsc::channel(EXPORT_BUFFER_ROWS);
+    tokio::spawn(async move {
+        if format == BulkFormat::Csv {
+            let header = csv_line(columns.iter().map(|column| column.to_string()));
+            if sender.send(Ok(header)).await.is_err() {
+                return;
+            }
+        }
+        let mut connection = match acquire_from(&pool).await {
+            Ok(connection) => connection,
+            Err(err) => {
+                let _ = sender.send(Err(err)).await;
+                return;
+            }
+        };
+        let mut rows = sqlx::query_as::<_, T>(sql).fetch(&mut *connection);
+        while let Some(row) = rows.next().await {
+            let line = row.map(|row| match format {
+                BulkFormat::Csv => csv_line(csv_fields(&row)),
+                BulkFormat::Ndjson => serde_json::to_string(&row).unwrap() + "\n",
+            });
+            let failed = line.is_err();
+            if sender.send(line).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+    ReceiverStream::new(receiver)
+}
+
+// Fields are quoted when they contain a separator, a quote or a line break
+pub fn csv_line(fields: impl IntoIterator<Item = String>) -> String {
+    let mut line = fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push('\n');
+    line
+}
+
+// Splits CSV into records along with the line each one starts on, blank lines are skipped.
+// Quoted fields may contain separators, doubled quotes and line breaks.
+fn csv_records(body: &str) -> Result<Vec<(usize, Vec<String>)>, ImportError> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut start = 1;
+    let mut quoted = false;
+    let mut chars = body.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => fields.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                if !(fields.len() == 1 && fields[0].is_empty()) {
+                    records.push((start, std::mem::take(&mut fields)));
+                }
+                fields.clear();
+                line += 1;
+                start = line;
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(ImportError {
+            line: start,
+            msg: "Quoted field is never closed".to_string(),
+        });
+    }
+    if !fields.is_empty() || !field.is_empty() {
+        fields.push(field);
+        records.push((start, fields));
+    }
+    Ok(records)
+}
+
+// A CSV record looked up by the header's column names
+struct CsvRow<'a> {
+    header: &'a [String],
+    fields: Vec<String>,
+}
+
+impl CsvRow<'_> {
+    // Empty fields count as missing
+    fn get(&self, column: &str) -> Option<&str> {
+        let index = self.header.iter().position(|name| name == column)?;
+        self.fields
+            .get(index)
+            .map(|field| field.as_str())
+            .filter(|field| !field.is_empty())
+    }
+
+    fn required<T: FromStr>(&self, column: &str) -> Result<T, String> {
+        self.optional(column)?
+            .ok_or_else(|| format!("{} is missing", column))
+    }
+
+    fn optional<T: FromStr>(&self, column: &str) -> Result<Option<T>, String> {
+        self.get(column)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("{} {:?} is not valid", column, value))
+            })
+            .transpose()
+    }
+}
+
+#[derive(Deserialize)]
+struct ImportTable {
+    id: u32,
+    seats: u32,
+}
+
+impl ImportTable {
+    const REQUIRED: [&'static str; 2] = ["id", "seats"];
+
+    fn from_csv(row: &CsvRow) -> Result<Self, String> {
+        Ok(ImportTable {
+            id: row.required("id")?,
+            seats: row.required("seats")?,
+        })
+    }
+}
+
+// Items get new ids, without a cook time one is picked like /items/add does
+#[derive(Deserialize)]
+struct ImportItem {
+    table_id: u32,
+    item: String,
+    cook_time: Option<u8>,
+    customer_id: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+impl ImportItem {
+    const REQUIRED: [&'static str; 2] = ["table_id", "item"];
+
+    fn from_csv(row: &CsvRow) -> Result<Self, String> {
+        Ok(ImportItem {
+            table_id: row.required("table_id")?,
+            item: row.required("item")?,
+            cook_time: row.optional("cook_time")?,
+            customer_id: row.optional("customer_id")?,
+            created_at: row.optional("created_at")?,
+        })
+    }
+}
+
+// Parses every row, the ones that fail are returned as errors instead
+fn parse_rows<T: DeserializeOwned>(
+    body: &str,
+    format: BulkFormat,
+    required: &[&str],
+    from_csv: fn(&CsvRow) -> Result<T, String>,
+) -> (Vec<(usize, T)>, Vec<ImportError>) {
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    match format {
+        BulkFormat::Csv => {
+            let records = match csv_records(body) {
+                Ok(records) => records,
+                Err(err) => return (rows, vec![err]),
+            };
+            let mut records = records.into_iter();
+            let Some((_, header)) = records.next() else {
+                return (rows, errors);
+            };
+            let header: Vec<String> = header.iter().map(|name| name.trim().to_string()).collect();
+            let missing: Vec<&str> = required
+                .iter()
+                .copied()
+                .filter(|column| !header.iter().any(|name| name == column))
+                .collect();
+            if !missing.is_empty() {
+                let msg = format!("Header is missing {}", missing.join(", "));
+                return (rows, vec![ImportError { line: 1, msg }]);
+            }
+
+            for (line, fields) in records {
+                if fields.len() != header.len() {
+                    let msg = format!("Expected {} fields, got {}", header.len(), fields.len());
+                    errors.push(ImportError { line, msg });
+                    continue;
+                }
+                match from_csv(&CsvRow {
+                    header: &header,
+                    fields,
+                }) {
+                    Ok(row) => rows.push((line, row)),
+                    Err(msg) => errors.push(ImportError { line, msg }),
+                }
+            }
+        }
+
+        BulkFormat::Ndjson => {
+            for (index, text) in body.lines().enumerate() {
+                if text.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(text) {
+                    Ok(row) => rows.push((index + 1, row)),
+                    Err(err) => {
+                        // serde_json adds the position within the line, the line number says enough
+                        let msg = err.to_string();
+                        let msg = match msg.rsplit_once(" at line ") {
+                            Some((msg, _)) => msg.to_string(),
+                            None => msg,
+                        };
+                        errors.push(ImportError {
+                            line: index + 1,
+                            msg: format!("Invalid JSON: {}", msg),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    (rows, errors)
+}
+
+// A validated row ready to insert
+enum Record {
+    Table(ImportTable),
+    Item(ImportItem),
+}
+
+impl Record {
+    // Inserts the row and returns the event announcing it, items are read back for their id
+    async fn insert(&self, conn: &mut MySqlConnection) -> Result<DomainEvent, sqlx::Error> {
+        match self {
+            Record::Table(table) => {
+                let version = next_table_version(&mut *conn).await?;
+                sqlx::query("INSERT INTO tables (id, seats, version) VALUES (?, ?, ?)")
+                    .bind(table.id)
+                    .bind(table.seats)
+                    .bind(version)
+                    .execute(conn)
+                    .await?;
+                Ok(DomainEvent::TableAdded {
+                    table: Table {
+                        id: table.id,
+                        seats: table.seats,
+                        version,
+                    },
+                })
+            }
+            Record::Item(item) => {
+                let result = sqlx::query(
+                    "INSERT INTO items (table_id, item, cook_time, customer_id, created_at) VALUES (?, ?, ?, ?, COALESCE(?, CURRENT_TIMESTAMP))",
+                )
+                .bind(item.table_id)
+                .bind(&item.item)
+                .bind(
+                    item.cook_time
+                        .unwrap_or_else(|| rand::thread_rng().gen_range(5..=15)),
+                )
+                .bind(&item.customer_id)
+                .bind(item.created_at)
+                .execute(&mut *conn)
+                .await?;
+                let item: Items = sqlx::query_as("SELECT * FROM items WHERE id = ?")
+                    .bind(result.last_insert_id())
+                    .fetch_one(conn)
+                    .await?;
+                Ok(DomainEvent::ItemAdded { item })
+            }
+        }
+    }
+}
+
+// Tables keep their ids, so an id that already exists or shows up twice is an error.
+// `publish` gets a TableAdded event for every table once it is committed.
+pub async fn import_tables(
+    app_database: &AppDatabase,
+    body: &str,
+    format: BulkFormat,
+    mode: ImportMode,
+    publish: impl Fn(DomainEvent),
+) -> Result<ImportReport, sqlx::Error> {
+    let (rows, mut errors) =
+        parse_rows(body, format, &ImportTable::REQUIRED, ImportTable::from_csv);
+    let existing: HashSet<u32> = sqlx::query_scalar("SELECT id FROM tables")
+        .fetch_all(&mut *app_database.acquire().await?)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut seen: HashMap<u32, usize> = HashMap::new();
+    let mut records = Vec::new();
+    for (line, table) in rows {
+        let problem = if existing.contains(&table.id) {
+            Some(format!("Table {} already exists", table.id))
+        } else {
+            seen.get(&table.id)
+                .map(|first| format!("Table {} is already on line {}", table.id, first))
+        };
+        match problem {
+            Some(msg) => errors.push(ImportError { line, msg }),
+            None => {
+                seen.insert(table.id, line);
+                records.push((line, Record::Table(table)));
+            }
+        }
+    }
+    import_records(app_database, records, errors, mode, publish).await
+}
+
+// Items go on tables that already exist, import the tables first.
+// `publish` gets an ItemAdded event for every item once it is committed.
+pub async fn import_items(
+    app_database: &AppDatabase,
+    body: &str,
+    format: BulkFormat,
+    mode: ImportMode,
+    publish: impl Fn(DomainEvent),
+) -> Result<ImportReport, sqlx::Error> {
+    let (rows, mut errors) = parse_rows(body, format, &ImportItem::REQUIRED, ImportItem::from_csv);
+    let tables: HashSet<u32> = sqlx::query_scalar("SELECT id FROM tables")
+        .fetch_all(&mut *app_database.acquire().await?)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut records = Vec::new();
+    for (line, item) in rows {
+        let problem = if item.item.trim().is_empty() {
+            Some("Item is empty".to_string())
+        } else if item.item.chars().count() > MAX_TEXT_CHARS {
+            Some(format!("Item is longer than {} characters", MAX_TEXT_CHARS))
+        } else if item
+            .customer_id
+            .as_ref()
+            .is_some_and(|customer_id| customer_id.chars().count() > MAX_TEXT_CHARS)
+        {
+            Some(format!(
+                "Customer id is longer than {} characters",
+                MAX_TEXT_CHARS
+            ))
+        } else if !tables.contains(&item.table_id) {
+            Some(format!("Table {} does not exist", item.table_id))
+        } else {
+            None
+        };
+        match problem {
+            Some(msg) => errors.push(ImportError { line, msg }),
+            None => records.push((line, Record::Item(item))),
+        }
+    }
+    import_records(app_database, records, errors, mode, publish).await
+}
+
+// Rows the database rejects, e.g. a table created since validation, are reported like invalid
+// ones. Anything else, like a lost connection, is returned as the error.
+async fn import_records(
+    app_database: &AppDatabase,
+    records: Vec<(usize, Record)>,
+    mut errors: Vec<ImportError>,
+    mode: ImportMode,
+    publish: impl Fn(DomainEvent),
+) -> Result<ImportReport, sqlx::Error> {
+    match mode {
+        ImportMode::AllOrNothing => {
+            if !errors.is_empty() {
+                return Ok(import_report(0, errors));
+            }
+            let mut tx = app_database.begin().await?;
+            let mut events = Vec::with_capacity(records.len());
+            for (line, record) in &records {
+                match record.insert(&mut tx).await {
+                    Ok(event) => events.push(event),
+                    Err(err) if err.as_database_error().is_some() => {
+                        // Dropping the transaction rolls back the rows before this one
+                        let msg = err.to_string();
+                        return Ok(import_report(0, vec![ImportError { line: *line, msg }]));
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            enqueue_deliveries(&mut tx, &events).await?;
+            tx.commit().await?;
+            // Only once committed, so subscribers never hear of rows that were rolled back
+            events.into_iter().for_each(publish);
+            Ok(import_report(records.len() as u64, errors))
+        }
+
+        ImportMode::BestEffort => {
+            let mut imported = 0;
+            for (line, record) in records {
+                // Every row commits on its own, so its event goes out straight away
+                let mut tx = app_database.begin().await?;
+                match record.insert(&mut tx).await {
+                    Ok(event) => {
+                        enqueue_deliveries(&mut tx, std::slice::from_ref(&event)).await?;
+                        tx.commit().await?;
+                        publish(event);
+                        imported += 1;
+                    }
+                    Err(err) if err.as_database_error().is_some() => {
+                        errors.push(ImportError {
+                            line,
+                            msg: err.to_string(),
+                        });
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(import_report(imported, errors))
+        }
+    }
+}
+
+fn import_report(imported: u64, mut errors: Vec<ImportError>) -> ImportReport {
+    errors.sort_by_key(|err| err.line);
+    let failed = errors.len() as u64;
+    errors.truncate(MAX_REPORTED_ERRORS);
+    ImportReport {
+        imported,
+        failed,
+        errors,
+    }
+}
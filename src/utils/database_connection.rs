@@ -1,20 +1,101 @@
-use sqlx::mysql::{MySqlPool, MySqlPoolOptions};
-use std::env;
+use sqlx::mysql::{MySql, MySqlPool, MySqlPoolOptions};
+use sqlx::pool::PoolConnection;
+use sqlx::Transaction;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::utils::config::DatabaseConfig;
+use crate::utils::metrics::record_pool_acquire;
+
+// Latest version recorded in the schema_migrations table by mysql_db/init.sql.
+// Existing databases are brought up to date with the scripts in mysql_db/migrations, see utils::migrations.
+// Bump together with any schema change so /health/ready catches a database that was not migrated.
+pub const EXPECTED_SCHEMA_VERSION: u32 = 6;
 
 pub struct AppDatabase {
     pub connection_pool: MySqlPool,
+    // Set once the first connection succeeds, until then /health/ready reports the database as down
+    connected: AtomicBool,
 }
 
-pub async fn database_connect() -> Result<AppDatabase, sqlx::Error> {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL env var is not set!");
+impl AppDatabase {
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    // Handlers take their connections through these two so the time spent waiting for one
+    // ends up in the pool acquire histogram
+    pub async fn acquire(&self) -> Result<PoolConnection<MySql>, sqlx::Error> {
+        acquire_from(&self.connection_pool).await
+    }
+
+    // Includes the round trip for BEGIN, which is small next to a wait for a free connection
+    pub async fn begin(&self) -> Result<Transaction<'static, MySql>, sqlx::Error> {
+        let started = Instant::now();
+        let tx = self.connection_pool.begin().await;
+        record_pool_acquire(started.elapsed());
+        tx
+    }
+}
+
+// For tasks that own a clone of the pool rather than the AppDatabase, like exports
+pub async fn acquire_from(pool: &MySqlPool) -> Result<PoolConnection<MySql>, sqlx::Error> {
+    let started = Instant::now();
+    let connection = pool.acquire().await;
+    record_pool_acquire(started.elapsed());
+    connection
+}
+
+// Builds the pool without connecting so the server can start before the database is up.
+// Only fails when the database url itself is invalid.
+pub fn database_connect(config: &DatabaseConfig) -> Result<AppDatabase, sqlx::Error> {
     // Note: Need URL encoding for a real database
     let pool = MySqlPoolOptions::new()
-        .max_connections(10)
-        .connect(&database_url)
-        .await?;
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
+        .idle_timeout(Duration::from_secs(config.idle_timeout_secs))
+        .connect_lazy(&config.url)?;
 
-    println!("Successfully connected to MySQL database!");
     Ok(AppDatabase {
         connection_pool: pool,
+        connected: AtomicBool::new(false),
     })
 }
+
+// Retries the first connection with exponential backoff, giving up once connect_max_wait_secs has passed
+pub async fn wait_for_database(
+    app_database: &AppDatabase,
+    config: &DatabaseConfig,
+) -> Result<(), sqlx::Error> {
+    let mut backoff = Duration::from_millis(config.connect_initial_backoff_ms);
+    let max_backoff = Duration::from_millis(config.connect_max_backoff_ms);
+    let max_wait = Duration::from_secs(config.connect_max_wait_secs);
+
+    let started = Instant::now();
+    let mut attempt = 1;
+    loop {
+        match app_database.connection_pool.acquire().await {
+            Ok(_) => {
+                app_database.connected.store(true, Ordering::Relaxed);
+                info!(attempt, "Successfully connected to MySQL database!");
+                return Ok(());
+            }
+
+            Err(err) if started.elapsed() + backoff < max_wait => {
+                warn!(
+                    attempt,
+                    retry_in_ms = backoff.as_millis() as u64,
+                    error = %err,
+                    "Database not available yet, retrying"
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(max_backoff);
+                attempt += 1;
+            }
+
+            Err(err) => return Err(err),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::header::IF_MATCH;
+use axum::http::request::Parts;
+use sqlx::mysql::MySqlConnection;
+use std::convert::Infallible;
+
+use crate::models::database::{Items, Table};
+
+// ETags carry the row id as well as its version, so a tag read for one item never matches
+// another item that happens to be at the same version
+pub fn table_etag(table: &Table) -> String {
+    format!("\"table-{}-v{}\"", table.id, table.version)
+}
+
+pub fn item_etag(item: &Items) -> String {
+    format!("\"item-{}-v{}\"", item.id, item.version)
+}
+
+// Clients pick table ids, so a table deleted and created again would start over at the same
+// version. Every table write draws its version from one sequence instead. The sequence row
+// stays locked until the caller's transaction ends, which serialises creating tables.
+pub async fn next_table_version(conn: &mut MySqlConnection) -> Result<u32, sqlx::Error> {
+    let result =
+        sqlx::query("UPDATE table_version_seq SET last_version = LAST_INSERT_ID(last_version + 1)")
+            .execute(conn)
+            .await?;
+    Ok(result.last_insert_id() as u32)
+}
+
+// The If-Match header of a request. Requests without one are unconditional.
+pub enum IfMatch {
+    Unconditional,
+    Any,
+    Tags(Vec<String>),
+}
+
+impl IfMatch {
+    pub fn parse(header: &str) -> IfMatch {
+        if header.trim() == "*" {
+            return IfMatch::Any;
+        }
+        IfMatch::Tags(
+            header
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+        )
+    }
+
+    // `current` is the ETag of the row as it is now, None when it does not exist.
+    // Weak tags never match since If-Match uses the strong comparison.
+    pub fn matches(&self, current: Option<&str>) -> bool {
+        match (self, current) {
+            (IfMatch::Unconditional, _) => true,
+            (IfMatch::Any, current) => current.is_some(),
+            (IfMatch::Tags(tags), Some(current)) => tags.iter().any(|tag| tag == current),
+            (IfMatch::Tags(_), None) => false,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // A header that is not valid ASCII can not match any of our tags
+        Ok(match parts.headers.get(IF_MATCH) {
+            Some(value) => IfMatch::parse(value.to_str().unwrap_or("")),
+            None => IfMatch::Unconditional,
+        })
+    }
+}
+
+// Outcome of a write guarded by If-Match. A failed precondition hands back the current ETag.
+pub enum Conditional<T> {
+    Applied(T),
+    PreconditionFailed(Option<String>),
+}
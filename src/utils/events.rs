@@ -0,0 +1,152 @@
+use chrono::Utc;
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::models::response::{DomainEvent, EventMessage};
+
+struct BusState {
+    next_id: u64,
+    // Most recent events, kept so reconnecting SSE clients can catch up
+    history: VecDeque<Arc<EventMessage>>,
+}
+
+// Events a reconnecting client missed, plus how many of them were too old to still be buffered
+pub struct Replay {
+    pub missed: u64,
+    pub events: Vec<Arc<EventMessage>>,
+}
+
+// Fans domain events out to every connected stream. Slow subscribers that fall more than the
+// channel capacity behind skip ahead and are told how many events they missed.
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<EventMessage>>,
+    history_size: usize,
+    // Held while sending so ids go out in order and replays line up with the live stream
+    state: Mutex<BusState>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize, history_size: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        EventBus {
+            sender,
+            history_size,
+            state: Mutex::new(BusState {
+                next_id: 1,
+                history: VecDeque::with_capacity(history_size),
+            }),
+        }
+    }
+
+    pub fn publish(&self, event: DomainEvent) {
+        let mut state = self.state.lock().unwrap();
+        let message = Arc::new(EventMessage {
+            id: state.next_id,
+            at: Utc::now(),
+            event,
+        });
+        state.next_id += 1;
+
+        if self.history_size > 0 {
+            if state.history.len() >= self.history_size {
+                state.history.pop_front();
+            }
+            state.history.push_back(message.clone());
+        }
+        // Sending only fails when nobody is subscribed, which is fine
+        let _ = self.sender.send(message);
+    }
+
+    pub fn publish_all(&self, events: impl IntoIterator<Item = DomainEvent>) {
+        for event in events {
+            self.publish(event);
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<EventMessage>> {
+        self.sender.subscribe()
+    }
+
+    // Subscribes and collects the buffered events after `last_event_id` in one step,
+    // so nothing is missed or sent twice between the replay and the live stream
+    pub fn subscribe_from(
+        &self,
+        last_event_id: u64,
+    ) -> (Replay, broadcast::Receiver<Arc<EventMessage>>) {
+        let state = self.state.lock().unwrap();
+        let receiver = self.sender.subscribe();
+
+        // An id that was never handed out comes from before a restart. Everything still buffered
+        // is replayed and the client is told it lagged, what it missed in between can't be counted.
+        let from_earlier_run = last_event_id >= state.next_id;
+        let last_event_id = if from_earlier_run { 0 } else { last_event_id };
+
+        let events: Vec<Arc<EventMessage>> = state
+            .history
+            .iter()
+            .filter(|message| message.id > last_event_id)
+            .cloned()
+            .collect();
+        // Ids are consecutive, anything between the client's last id and the oldest buffered one is gone
+        let oldest_id = state
+            .history
+            .front()
+            .map_or(state.next_id, |message| message.id);
+        let missed = oldest_id.saturating_sub(last_event_id.saturating_add(1));
+        let missed = if from_earlier_run {
+            missed.max(1)
+        } else {
+            missed
+        };
+
+        (Replay { missed, events }, receiver)
+    }
+}
+
+// Which tables a stream wants events for
+#[derive(Debug, Clone)]
+pub enum Subscription {
+    Kitchen,
+    Tables(BTreeSet<u32>),
+}
+
+impl Subscription {
+    pub fn new(tables: Option<Vec<u32>>) -> Self {
+        match tables {
+            Some(tables) => Subscription::Tables(tables.into_iter().collect()),
+            None => Subscription::Kitchen,
+        }
+    }
+
+    // Parses the `tables` query parameter, e.g. "1,2,3"
+    pub fn parse(tables: Option<&str>) -> Result<Self, String> {
+        let Some(tables) = tables else {
+            return Ok(Subscription::Kitchen);
+        };
+        tables
+            .split(',')
+            .map(|table_id| {
+                table_id
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid table id {:?} in tables", table_id))
+            })
+            .collect::<Result<Vec<u32>, String>>()
+            .map(|tables| Subscription::new(Some(tables)))
+    }
+
+    pub fn matches(&self, event: &DomainEvent) -> bool {
+        match self {
+            Subscription::Kitchen => true,
+            Subscription::Tables(tables) => tables.contains(&event.table_id()),
+        }
+    }
+
+    pub fn tables(&self) -> Option<Vec<u32>> {
+        match self {
+            Subscription::Kitchen => None,
+            Subscription::Tables(tables) => Some(tables.iter().copied().collect()),
+        }
+    }
+}
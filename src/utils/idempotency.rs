@@ -0,0 +1,274 @@
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{
+        header::{CONTENT_TYPE, ETAG, LOCATION},
+        HeaderName, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::utils::auth::{hash_api_key, Principal, API_KEY_HEADER};
+use crate::utils::response_builder::{
+    idempotency_conflict_response, idempotency_in_progress_response,
+    invalid_idempotency_key_response, payload_too_large_response,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+// Set on responses replayed from the store
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+const MAX_KEY_LENGTH: usize = 255;
+// Expired entries are swept at most this often, on the next request that comes in
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+// Upper bound on the keys held, past it the least recently used finished keys are dropped
+// even if they have not expired yet
+const MAX_TRACKED_KEYS: usize = 10_000;
+// Headers a replay carries over from the stored response
+const REPLAYED_HEADERS: [HeaderName; 3] = [CONTENT_TYPE, ETAG, LOCATION];
+
+#[derive(Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: Bytes,
+}
+
+enum EntryState {
+    InProgress,
+    Done(StoredResponse),
+}
+
+struct Entry {
+    // Hash of the method, path, query and body the key was first used with
+    fingerprint: Vec<u8>,
+    created_at: Instant,
+    last_used: Instant,
+    state: EntryState,
+}
+
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    last_pruned: Instant,
+}
+
+// In-memory record of responses to requests sent with an Idempotency-Key header.
+// Keys are scoped to the caller so two clients can not replay each other's responses.
+// The store is per process: it is lost on restart and not shared between instances, so a retry
+// that lands on another instance or after a restart runs the request again.
+pub struct IdempotencyStore {
+    ttl: Duration,
+    max_body_bytes: usize,
+    entries: Mutex<Entries>,
+}
+
+enum Lookup {
+    New,
+    Replay(StoredResponse),
+    InProgress,
+    Mismatch,
+}
+
+impl IdempotencyStore {
+    pub fn new(ttl: Duration, max_body_bytes: usize) -> Self {
+        IdempotencyStore {
+            ttl,
+            max_body_bytes,
+            entries: Mutex::new(Entries {
+                by_key: HashMap::new(),
+                last_pruned: Instant::now(),
+            }),
+        }
+    }
+
+    // Claims the key for this request unless it was already used
+    fn begin(&self, key: &str, fingerprint: &[u8]) -> Lookup {
+        let mut guard = self.entries.lock().unwrap();
+        let Entries {
+            by_key: entries,
+            last_pruned,
+        } = &mut *guard;
+        if last_pruned.elapsed() >= PRUNE_INTERVAL || entries.len() >= MAX_TRACKED_KEYS {
+            self.prune(entries);
+            *last_pruned = Instant::now();
+        }
+
+        match entries.get_mut(key) {
+            Some(entry) if entry.created_at.elapsed() <= self.ttl => {
+                entry.last_used = Instant::now();
+                if entry.fingerprint != fingerprint {
+                    Lookup::Mismatch
+                } else {
+                    match &entry.state {
+                        EntryState::InProgress => Lookup::InProgress,
+                        EntryState::Done(stored) => Lookup::Replay(stored.clone()),
+                    }
+                }
+            }
+            _ => {
+                let now = Instant::now();
+                entries.insert(
+                    key.to_string(),
+                    Entry {
+                        fingerprint: fingerprint.to_vec(),
+                        created_at: now,
+                        last_used: now,
+                        state: EntryState::InProgress,
+                    },
+                );
+                Lookup::New
+            }
+        }
+    }
+
+    // Drops expired keys, then the least recently used finished ones while still at the cap.
+    // Keys in progress are kept so a request can never run twice at once.
+    fn prune(&self, entries: &mut HashMap<String, Entry>) {
+        entries.retain(|_, entry| entry.created_at.elapsed() <= self.ttl);
+        if entries.len() < MAX_TRACKED_KEYS {
+            return;
+        }
+        let mut finished: Vec<(Instant, String)> = entries
+            .iter()
+            .filter(|(_, entry)| matches!(entry.state, EntryState::Done(_)))
+            .map(|(key, entry)| (entry.last_used, key.clone()))
+            .collect();
+        finished.sort_unstable();
+        // Down to 90% so the next few new keys don't each pay for a full sweep
+        let excess = entries.len() - MAX_TRACKED_KEYS * 9 / 10;
+        for (_, key) in finished.into_iter().take(excess) {
+            entries.remove(&key);
+        }
+    }
+
+    fn complete(&self, key: &str, stored: StoredResponse) {
+        if let Some(entry) = self.entries.lock().unwrap().by_key.get_mut(key) {
+            entry.state = EntryState::Done(stored);
+        }
+    }
+
+    fn release(&self, key: &str) {
+        self.entries.lock().unwrap().by_key.remove(key);
+    }
+}
+
+// Frees the key if the request never finishes, e.g. the client hung up mid request
+struct InProgressGuard<'a> {
+    store: &'a IdempotencyStore,
+    key: &'a str,
+    completed: bool,
+}
+
+impl Drop for InProgressGuard<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.store.release(self.key);
+        }
+    }
+}
+
+// Runs after authentication on the protected routes. Requests without the header pass straight
+// through. Server errors are not stored so a retry can run the request again.
+pub async fn idempotency(
+    State(store): State<Arc<IdempotencyStore>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mutating = matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    );
+    let idempotency_key = match request.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) if mutating => match value.to_str() {
+            Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+            _ => return invalid_idempotency_key_response(MAX_KEY_LENGTH),
+        },
+        _ => return next.run(request).await,
+    };
+
+    let caller = match request.extensions().get::<Principal>() {
+        Some(Principal::Staff(claims)) => format!("staff:{}", claims.sub),
+        _ => {
+            let api_key = request
+                .headers()
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            format!("key:{}", hash_api_key(api_key))
+        }
+    };
+    let key = format!("{}:{}", caller, idempotency_key);
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, store.max_body_bytes).await {
+        Ok(body) => body,
+        Err(_) => return payload_too_large_response(store.max_body_bytes),
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(parts.method.as_str().as_bytes());
+    // The query is part of the request, an import with another mode is another request
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or_else(|| parts.uri.path());
+    hasher.update(path_and_query.as_bytes());
+    hasher.update(&body);
+    let fingerprint = hasher.finalize();
+
+    match store.begin(&key, &fingerprint) {
+        Lookup::New => {}
+        Lookup::Replay(stored) => return replay(stored),
+        Lookup::InProgress => return idempotency_in_progress_response(),
+        Lookup::Mismatch => return idempotency_conflict_response(),
+    }
+
+    let mut guard = InProgressGuard {
+        store: &store,
+        key: &key,
+        completed: false,
+    };
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if response.status().is_server_error() {
+        return response;
+    }
+
+    // Handler responses are small JSON bodies, so buffering them is cheap
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    store.complete(
+        &key,
+        StoredResponse {
+            status: parts.status,
+            headers: REPLAYED_HEADERS
+                .iter()
+                .filter_map(|name| {
+                    let value = parts.headers.get(name)?;
+                    Some((name.clone(), value.clone()))
+                })
+                .collect(),
+            body: body.clone(),
+        },
+    );
+    guard.completed = true;
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::new(Body::from(stored.body));
+    *response.status_mut() = stored.status;
+    for (name, value) in stored.headers {
+        response.headers_mut().insert(name, value);
+    }
+    response
+        .headers_mut()
+        .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
@@ -0,0 +1,122 @@
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::MySqlConnection;
+
+use crate::models::database::Role;
+use crate::models::response::TokenResponse;
+use crate::utils::auth::random_hex;
+use crate::utils::config::AuthConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    Access,
+    Refresh,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Claims {
+    // Staff id
+    pub sub: u32,
+    pub username: String,
+    pub role: Role,
+    // Unique token id, used to revoke a single token
+    pub jti: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub typ: TokenType,
+}
+
+pub struct JwtKeys {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+}
+
+impl JwtKeys {
+    pub fn new(config: &AuthConfig) -> Self {
+        let secret = config.jwt_secret.as_bytes();
+        JwtKeys {
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            access_ttl: Duration::seconds(config.access_token_ttl_secs),
+            refresh_ttl: Duration::seconds(config.refresh_token_ttl_secs),
+        }
+    }
+
+    fn issue(
+        &self,
+        staff_id: u32,
+        username: &str,
+        role: Role,
+        typ: TokenType,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        let now = Utc::now();
+        let ttl = match typ {
+            TokenType::Access => self.access_ttl,
+            TokenType::Refresh => self.refresh_ttl,
+        };
+        let claims = Claims {
+            sub: staff_id,
+            username: username.to_string(),
+            role,
+            jti: random_hex(16),
+            iat: now.timestamp(),
+            exp: (now + ttl).timestamp(),
+            typ,
+        };
+        encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key)
+    }
+
+    pub fn issue_pair(
+        &self,
+        staff_id: u32,
+        username: &str,
+        role: Role,
+    ) -> Result<TokenResponse, jsonwebtoken::errors::Error> {
+        Ok(TokenResponse {
+            access_token: self.issue(staff_id, username, role, TokenType::Access)?,
+            refresh_token: self.issue(staff_id, username, role, TokenType::Refresh)?,
+            token_type: "Bearer".to_string(),
+            expires_in: self.access_ttl.num_seconds(),
+        })
+    }
+
+    // Checks the signature, expiry and token type. Revocation is checked separately against the database.
+    pub fn verify(&self, token: &str, typ: TokenType) -> Option<Claims> {
+        decode::<Claims>(
+            token,
+            &self.decoding_key,
+            &Validation::new(Algorithm::HS256),
+        )
+        .ok()
+        .map(|data| data.claims)
+        .filter(|claims| claims.typ == typ)
+    }
+}
+
+pub async fn is_revoked(conn: &mut MySqlConnection, jti: &str) -> Result<bool, sqlx::Error> {
+    let revoked: Option<(String,)> = sqlx::query_as("SELECT jti FROM revoked_tokens WHERE jti = ?")
+        .bind(jti)
+        .fetch_optional(conn)
+        .await?;
+    Ok(revoked.is_some())
+}
+
+// Returns false when the token was already revoked.
+// Revoked ids only need to be kept until the token would have expired anyway.
+pub async fn revoke(conn: &mut MySqlConnection, claims: &Claims) -> Result<bool, sqlx::Error> {
+    sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < CURRENT_TIMESTAMP")
+        .execute(&mut *conn)
+        .await?;
+    let result = sqlx::query(
+        "INSERT IGNORE INTO revoked_tokens (jti, expires_at) VALUES (?, FROM_UNIXTIME(?))",
+    )
+    .bind(&claims.jti)
+    .bind(claims.exp)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
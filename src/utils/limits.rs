@@ -0,0 +1,151 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{
+        header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER},
+        StatusCode,
+    },
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::utils::auth::{hash_api_key, API_KEY_HEADER};
+use crate::utils::config::LimitsConfig;
+use crate::utils::response_builder::{payload_too_large_response, too_many_requests_response};
+
+// Idle buckets are pruned once this many clients are being tracked
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+// Token bucket per client: holds up to `burst` requests and refills at `per_sec` requests a second
+pub struct RateLimiter {
+    burst: f64,
+    per_sec: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(burst: f64, per_sec: f64) -> Self {
+        RateLimiter {
+            burst,
+            per_sec,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Takes a token for the client, or returns how long until one is available
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            let (burst, per_sec) = (self.burst, self.per_sec);
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.last_refill).as_secs_f64() * per_sec
+                    < burst
+            });
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+        let refill = now.duration_since(bucket.last_refill).as_secs_f64() * self.per_sec;
+        bucket.tokens = (bucket.tokens + refill).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_sec,
+            ))
+        }
+    }
+}
+
+pub struct Limits {
+    pub max_body_bytes: usize,
+    pub max_items_per_request: usize,
+    pub max_page_size: usize,
+    ip_limiter: RateLimiter,
+    key_limiter: RateLimiter,
+}
+
+impl Limits {
+    pub fn new(config: &LimitsConfig) -> Self {
+        Limits {
+            max_body_bytes: config.max_body_bytes,
+            max_items_per_request: config.max_items_per_request,
+            max_page_size: config.max_page_size,
+            ip_limiter: RateLimiter::new(config.rate_limit_ip_burst, config.rate_limit_ip_per_sec),
+            key_limiter: RateLimiter::new(
+                config.rate_limit_key_burst,
+                config.rate_limit_key_per_sec,
+            ),
+        }
+    }
+}
+
+// Runs before authentication so a looping client is turned away without touching the database.
+// Requests are limited per source IP, and per API key or bearer token when one is presented.
+pub async fn rate_limit(
+    State(limits): State<Arc<Limits>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(retry_after) = limits.ip_limiter.check(&addr.ip().to_string()) {
+        return with_retry_after(retry_after);
+    }
+
+    let headers = request.headers();
+    let credential = headers
+        .get(API_KEY_HEADER)
+        .or_else(|| headers.get(AUTHORIZATION))
+        .and_then(|value| value.to_str().ok());
+    if let Some(credential) = credential {
+        // Hashed so raw keys are not kept in memory longer than needed
+        if let Err(retry_after) = limits.key_limiter.check(&hash_api_key(credential)) {
+            return with_retry_after(retry_after);
+        }
+    }
+
+    next.run(request).await
+}
+
+// Body limit rejections come from the extractors as plain text, swap them for the JSON envelope
+// every other error uses. 413s the handlers build themselves are already enveloped.
+pub async fn payload_too_large_envelope(
+    State(limits): State<Arc<Limits>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    let is_plain_text = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain"));
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && is_plain_text {
+        return payload_too_large_response(limits.max_body_bytes);
+    }
+    response
+}
+
+fn with_retry_after(retry_after: Duration) -> Response {
+    let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let mut response = too_many_requests_response(secs);
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(secs));
+    response
+}
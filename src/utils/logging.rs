@@ -0,0 +1,67 @@
+use axum::{
+    extract::MatchedPath,
+    http::{HeaderName, Request, Response},
+};
+use std::time::Duration;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tracing::{info_span, Span};
+use tracing_subscriber::EnvFilter;
+
+use crate::utils::config::{LogFormat, LoggingConfig};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// sqlx logs every statement with its elapsed time at debug level under sqlx::query
+const DEFAULT_LOG_FILTER: &str = "info,sqlx::query=debug";
+
+// LOG_FORMAT picks between human readable ("pretty", the default) and "json" output.
+// RUST_LOG overrides the default filter, e.g. RUST_LOG=debug or RUST_LOG=warn,sqlx=off
+pub fn init_logging(config: &LoggingConfig) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(config.filter.as_deref().unwrap_or(DEFAULT_LOG_FILTER)));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match config.format {
+        LogFormat::Json => builder.json().with_current_span(true).init(),
+        LogFormat::Pretty => builder.init(),
+    }
+}
+
+pub fn set_request_id_layer() -> SetRequestIdLayer<MakeRequestUuid> {
+    SetRequestIdLayer::new(HeaderName::from_static(REQUEST_ID_HEADER), MakeRequestUuid)
+}
+
+// Echoes the request id back so clients can quote it when reporting problems
+pub fn propagate_request_id_layer() -> PropagateRequestIdLayer {
+    PropagateRequestIdLayer::new(HeaderName::from_static(REQUEST_ID_HEADER))
+}
+
+// One span per request carrying the request id and matched route. Everything logged while
+// handling the request, sqlx statements included, is recorded inside it.
+pub fn make_request_span<B>(request: &Request<B>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched_path| matched_path.as_str())
+        .unwrap_or("unmatched");
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+
+    info_span!(
+        "request",
+        request_id = request_id,
+        method = %request.method(),
+        route = route,
+        status = tracing::field::Empty,
+        latency_ms = tracing::field::Empty,
+    )
+}
+
+pub fn log_response<B>(response: &Response<B>, latency: Duration, span: &Span) {
+    span.record("status", response.status().as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+    tracing::info!("finished request");
+}
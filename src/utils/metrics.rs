@@ -0,0 +1,76 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use metrics::{counter, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::time::{Duration, Instant};
+
+const REQUEST_DURATION_METRIC: &str = "http_request_duration_seconds";
+// Most requests are a single indexed query, so the buckets are weighted towards the low end
+const REQUEST_DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+const POOL_ACQUIRE_METRIC: &str = "db_pool_acquire_seconds";
+// An idle connection is handed out in microseconds, the upper buckets catch an exhausted pool
+// running into DB_ACQUIRE_TIMEOUT_SECS
+const POOL_ACQUIRE_BUCKETS: &[f64] = &[
+    0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0,
+];
+
+// Installs the global recorder. The handle renders everything recorded so far in Prometheus format.
+pub fn install_metrics_recorder() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(REQUEST_DURATION_METRIC.to_string()),
+            REQUEST_DURATION_BUCKETS,
+        )
+        .expect("Request duration buckets are not empty")
+        .set_buckets_for_metric(
+            Matcher::Full(POOL_ACQUIRE_METRIC.to_string()),
+            POOL_ACQUIRE_BUCKETS,
+        )
+        .expect("Pool acquire buckets are not empty")
+        .install_recorder()
+        .expect("Failed to install metrics recorder")
+}
+
+// Records a count, a latency observation and, for 4xx and 5xx, an error count for every request
+pub async fn track_metrics(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched_path| matched_path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(request).await;
+
+    let status = response.status();
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", status.as_u16().to_string()),
+    ];
+    counter!("http_requests_total", &labels).increment(1);
+    histogram!(REQUEST_DURATION_METRIC, &labels[..2]).record(start.elapsed().as_secs_f64());
+
+    if status.is_client_error() || status.is_server_error() {
+        let class = format!("{}xx", status.as_u16() / 100);
+        counter!(
+            "http_errors_total",
+            "route" => labels[1].1.clone(),
+            "class" => class
+        )
+        .increment(1);
+    }
+
+    response
+}
+
+// Time a handler waited for a database connection, failed attempts included
+pub fn record_pool_acquire(waited: Duration) {
+    histogram!(POOL_ACQUIRE_METRIC).record(waited.as_secs_f64());
+}
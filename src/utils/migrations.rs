@@ -0,0 +1,135 @@
+use sqlx::mysql::MySqlPool;
+use sqlx::Executor;
+
+// Full schema for a new database, already at the latest version
+const INIT_SQL: &str = include_str!("../../mysql_db/init.sql");
+
+// Version 1 is the schema from before schema_migrations existed, just tables and items
+const SCHEMA_MIGRATIONS_SQL: &str = "CREATE TABLE schema_migrations (
+    version INTEGER UNSIGNED PRIMARY KEY,
+    applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+)";
+
+// Scripts that bring an existing database up from the previous version, in order.
+// Every schema change adds one here and to mysql_db/migrations, folds it into init.sql and
+// bumps EXPECTED_SCHEMA_VERSION.
+pub const MIGRATIONS: [(u32, &str); 5] = [
+    (
+        2,
+        include_str!("../../mysql_db/migrations/0002_webhooks.sql"),
+    ),
+    (
+        3,
+        include_str!("../../mysql_db/migrations/0003_versions.sql"),
+    ),
+    (4, include_str!("../../mysql_db/migrations/0004_auth.sql")),
+    (
+        5,
+        include_str!("../../mysql_db/migrations/0005_table_versions.sql"),
+    ),
+    (
+        6,
+        include_str!("../../mysql_db/migrations/0006_item_sales.sql"),
+    ),
+];
+
+// What a call to `migrate` did
+pub enum Migrated {
+    // The database was empty and got the schema from init.sql
+    Created,
+    // The database predates schema_migrations. It was recorded at the version its tables
+    // match, then these versions were applied.
+    Baselined { baseline: u32, applied: Vec<u32> },
+    // These versions were applied, none when it was already up to date
+    Applied(Vec<u32>),
+}
+
+// Highest version in schema_migrations, None for a database without the table
+pub async fn schema_version(pool: &MySqlPool) -> Result<Option<u32>, sqlx::Error> {
+    if !table_exists(pool, "schema_migrations").await? {
+        return Ok(None);
+    }
+    let (version,): (Option<u32>,) = sqlx::query_as("SELECT MAX(version) FROM schema_migrations")
+        .fetch_one(pool)
+        .await?;
+    Ok(version)
+}
+
+// Creates the schema in an empty database or applies the migrations it is missing.
+// MySQL commits DDL straight away, so a script that fails halfway leaves its earlier statements applied.
+pub async fn migrate(pool: &MySqlPool) -> Result<Migrated, sqlx::Error> {
+    if let Some(version) = schema_version(pool).await? {
+        return Ok(Migrated::Applied(apply_migrations(pool, version).await?));
+    }
+
+    let Some(baseline) = baseline_version(pool).await? else {
+        execute_script(pool, INIT_SQL).await?;
+        return Ok(Migrated::Created);
+    };
+    pool.execute(SCHEMA_MIGRATIONS_SQL).await?;
+    sqlx::query("INSERT INTO schema_migrations (version) VALUES (?)")
+        .bind(baseline)
+        .execute(pool)
+        .await?;
+    let applied = apply_migrations(pool, baseline).await?;
+    Ok(Migrated::Baselined { baseline, applied })
+}
+
+async fn apply_migrations(pool: &MySqlPool, version: u32) -> Result<Vec<u32>, sqlx::Error> {
+    let mut applied = Vec::new();
+    for (target, script) in MIGRATIONS.iter().filter(|(target, _)| *target > version) {
+        execute_script(pool, script).await?;
+        applied.push(*target);
+    }
+    Ok(applied)
+}
+
+// The version a database without schema_migrations is at, judged by its tables. None when it
+// has no tables at all. The auth tables came before the webhooks but are created with
+// IF NOT EXISTS by their migration, so they don't need to be told apart here.
+async fn baseline_version(pool: &MySqlPool) -> Result<Option<u32>, sqlx::Error> {
+    if !table_exists(pool, "tables").await? {
+        return Ok(None);
+    }
+    let (has_versions,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM information_schema.columns WHERE table_schema = DATABASE() AND table_name = 'tables' AND column_name = 'version'",
+    )
+    .fetch_one(pool)
+    .await?;
+    let version = if has_versions > 0 {
+        3
+    } else if table_exists(pool, "webhook_outbox").await? {
+        2
+    } else {
+        1
+    };
+    Ok(Some(version))
+}
+
+async fn table_exists(pool: &MySqlPool, table: &str) -> Result<bool, sqlx::Error> {
+    let (exists,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = DATABASE() AND table_name = ?",
+    )
+    .bind(table)
+    .fetch_one(pool)
+    .await?;
+    Ok(exists > 0)
+}
+
+// Runs the statements of a script one by one. Statements are split on ';', so a script can't
+// use one inside a string or a comment, and lines starting with "--" are dropped.
+pub async fn execute_script(pool: &MySqlPool, script: &str) -> Result<(), sqlx::Error> {
+    let script = script
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("--"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    for statement in script
+        .split(';')
+        .filter(|statement| !statement.trim().is_empty())
+    {
+        // Sent as a plain query, some DDL can't be prepared
+        pool.execute(statement).await?;
+    }
+    Ok(())
+}
@@ -1,2 +1,19 @@
+pub mod app_state;
+pub mod auth;
+pub mod bulk;
+pub mod config;
 pub mod database_connection;
+pub mod etag;
+pub mod events;
+pub mod idempotency;
+pub mod jwt;
+pub mod limits;
+pub mod logging;
+pub mod metrics;
+pub mod migrations;
+pub mod pagination;
 pub mod response_builder;
+pub mod sales;
+pub mod shutdown;
+pub mod undo_stack;
+pub mod webhooks;
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::MySql;
+use sqlx::QueryBuilder;
+
+use crate::models::database::Items;
+use crate::models::request::{ItemSort, SortDirection};
+
+// Sort value of the last item on a page, tagged with the field it belongs to
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "sort", content = "value", rename_all = "snake_case")]
+enum SortKey {
+    CreatedAt(DateTime<Utc>),
+    CookTime(u8),
+    Item(String),
+}
+
+// Where the next page starts. Handed to clients as an opaque hex string.
+#[derive(Debug, Serialize, Deserialize)]
+struct ItemCursor {
+    key: SortKey,
+    direction: SortDirection,
+    id: u32,
+}
+
+// A page of items. Pages are keyset based on (sort field, id) so rows added while a client is
+// paging do not shift the pages it has not read yet.
+pub struct Page {
+    sort: ItemSort,
+    direction: SortDirection,
+    limit: usize,
+    after: Option<ItemCursor>,
+}
+
+impl Page {
+    pub fn new(
+        sort: ItemSort,
+        direction: SortDirection,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+        max_page_size: usize,
+    ) -> Result<Page, String> {
+        let limit = limit.unwrap_or(max_page_size);
+        if limit == 0 || limit > max_page_size {
+            return Err(format!("limit must be between 1 and {}", max_page_size));
+        }
+
+        let after = match cursor {
+            Some(cursor) => {
+                let cursor = decode_cursor(cursor).ok_or("cursor is not valid".to_string())?;
+                if sort_of(&cursor.key) != sort || cursor.direction != direction {
+                    return Err("cursor was issued for a different sort".to_string());
+                }
+                Some(cursor)
+            }
+            None => None,
+        };
+
+        Ok(Page {
+            sort,
+            direction,
+            limit,
+            after,
+        })
+    }
+
+    // Appends the keyset condition, ordering and limit to a query over `items` that already has a
+    // WHERE clause. One extra row is fetched to tell whether there is a next page.
+    pub fn push_to(&self, query: &mut QueryBuilder<'_, MySql>) {
+        let column = sort_column(self.sort);
+        let (compare, order) = match self.direction {
+            SortDirection::Asc => (" > ", " ASC"),
+            SortDirection::Desc => (" < ", " DESC"),
+        };
+
+        if let Some(after) = &self.after {
+            query.push(format!(" AND ({}{}", column, compare));
+            push_key(query, &after.key);
+            query.push(format!(" OR ({} = ", column));
+            push_key(query, &after.key);
+            query.push(format!(" AND id{}", compare));
+            query.push_bind(after.id);
+            query.push("))");
+        }
+
+        query.push(format!(" ORDER BY {}{}, id{}", column, order, order));
+        query.push(" LIMIT ");
+        query.push_bind((self.limit + 1) as u64);
+    }
+
+    // Drops the extra row fetched by push_to and returns the cursor for the next page, if any
+    pub fn next_cursor(&self, items: &mut Vec<Items>) -> Option<String> {
+        if items.len() <= self.limit {
+            return None;
+        }
+        items.truncate(self.limit);
+        let last = items.last()?;
+        let key = match self.sort {
+            ItemSort::CreatedAt => SortKey::CreatedAt(last.created_at),
+            ItemSort::CookTime => SortKey::CookTime(last.cook_time),
+            ItemSort::Item => SortKey::Item(last.item.clone()),
+        };
+        Some(encode_cursor(&ItemCursor {
+            key,
+            direction: self.direction,
+            id: last.id,
+        }))
+    }
+}
+
+fn sort_column(sort: ItemSort) -> &'static str {
+    match sort {
+        ItemSort::CreatedAt => "created_at",
+        ItemSort::CookTime => "cook_time",
+        ItemSort::Item => "item",
+    }
+}
+
+fn sort_of(key: &SortKey) -> ItemSort {
+    match key {
+        SortKey::CreatedAt(_) => ItemSort::CreatedAt,
+        SortKey::CookTime(_) => ItemSort::CookTime,
+        SortKey::Item(_) => ItemSort::Item,
+    }
+}
+
+fn push_key(query: &mut QueryBuilder<'_, MySql>, key: &SortKey) {
+    match key {
+        SortKey::CreatedAt(created_at) => query.push_bind(*created_at),
+        SortKey::CookTime(cook_time) => query.push_bind(*cook_time),
+        SortKey::Item(item) => query.push_bind(item.clone()),
+    };
+}
+
+fn encode_cursor(cursor: &ItemCursor) -> String {
+    serde_json::to_vec(cursor)
+        .unwrap_or_default()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode_cursor(cursor: &str) -> Option<ItemCursor> {
+    let bytes = cursor
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    serde_json::from_slice(&bytes).ok()
+}
@@ -1,12 +1,15 @@
 use axum::body::Body;
-use axum::http::{Response, StatusCode};
+use axum::http::header::ETAG;
+use axum::http::{HeaderValue, Response, StatusCode};
 use axum::response::IntoResponse;
+use axum::Json;
 use sqlx::error::Error;
 use sqlx::mysql::MySqlQueryResult;
 
-use crate::models::database::Table;
-use crate::models::request::{GetItemRequest, TableItem};
-use crate::models::response::GenericResponse;
+use crate::models::database::{Scope, Table};
+use crate::models::request::{CreateApiKeyRequest, GetItemRequest, TableItem};
+use crate::models::response::{GenericResponse, ImportReport};
+use crate::utils::undo_stack::UndoEntry;
 
 // Success Responses
 pub trait TableSuccessResponseBuilder {
@@ -109,6 +112,8 @@ impl TableErrorResponseBuilder for Error {
 
 pub trait ItemErrorResponseBuilder {
     fn get_items_err(&self, body: GetItemRequest) -> GenericResponse;
+    fn get_item_err(&self, item_id: u32) -> GenericResponse;
+    fn search_items_err(&self) -> GenericResponse;
     fn delete_by_id_err(&self, item_id: u32) -> GenericResponse;
     fn delete_item_err(&self, body: TableItem) -> GenericResponse;
     fn add_items_err(&self) -> GenericResponse;
@@ -128,6 +133,22 @@ impl ItemErrorResponseBuilder for Error {
         }
     }
 
+    fn get_item_err(&self, item_id: u32) -> GenericResponse {
+        GenericResponse {
+            msg: format!("Error when attempting to get item {}", item_id),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn search_items_err(&self) -> GenericResponse {
+        GenericResponse {
+            msg: "Error when attempting to search items".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
     fn delete_by_id_err(&self, item_id: u32) -> GenericResponse {
         // This should basically never happen since we allow deleting items that dont exist
         GenericResponse {
@@ -157,3 +178,538 @@ impl ItemErrorResponseBuilder for Error {
         }
     }
 }
+
+pub fn item_not_found_response(item_id: u32) -> Response<Body> {
+    GenericResponse {
+        msg: format!("Item {} does not exist", item_id),
+        status_code: StatusCode::NOT_FOUND.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn docs_asset_not_found_response(asset: &str) -> Response<Body> {
+    GenericResponse {
+        msg: format!("Docs asset {} does not exist", asset),
+        status_code: StatusCode::NOT_FOUND.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn invalid_page_response(reason: String) -> Response<Body> {
+    GenericResponse {
+        msg: reason,
+        status_code: StatusCode::BAD_REQUEST.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn invalid_search_response(reason: String) -> Response<Body> {
+    GenericResponse {
+        msg: reason,
+        status_code: StatusCode::BAD_REQUEST.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+// Conditional request responses
+pub fn precondition_failed_response(resource: String, current: Option<String>) -> Response<Body> {
+    let msg = match current {
+        Some(_) => format!("{} has changed since it was read", resource),
+        None => format!("{} does not exist", resource),
+    };
+    let mut response = GenericResponse {
+        msg,
+        status_code: StatusCode::PRECONDITION_FAILED.as_u16(),
+        rows: None,
+    }
+    .into_response();
+    // Lets the client retry against the current version without another read
+    if let Some(etag) = current.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+        response.headers_mut().insert(ETAG, etag);
+    }
+    response
+}
+
+// Undo responses
+pub trait UndoSuccessResponseBuilder {
+    fn undo_response(&self, table_id: u32, rows: u64) -> Response<Body>;
+}
+
+impl UndoSuccessResponseBuilder for UndoEntry {
+    fn undo_response(&self, table_id: u32, rows: u64) -> Response<Body> {
+        let msg = match self {
+            UndoEntry::Items(items) => {
+                format!("Restored {} item(s) for table {}", items.len(), table_id)
+            }
+            UndoEntry::Table { items, .. } => {
+                format!("Restored table {} with {} item(s)", table_id, items.len())
+            }
+        };
+        GenericResponse {
+            msg,
+            status_code: StatusCode::OK.as_u16(),
+            rows: Some(rows),
+        }
+        .into_response()
+    }
+}
+
+pub fn nothing_to_undo_response(table_id: u32) -> Response<Body> {
+    GenericResponse {
+        msg: format!("Nothing to undo for table {}", table_id),
+        status_code: StatusCode::NOT_FOUND.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub trait UndoErrorResponseBuilder {
+    fn undo_err(&self, table_id: u32) -> GenericResponse;
+}
+
+impl UndoErrorResponseBuilder for Error {
+    fn undo_err(&self, table_id: u32) -> GenericResponse {
+        // Most likely the table or item ids were re-used after the delete
+        GenericResponse {
+            msg: format!(
+                "Error when attempting to undo last delete for table {}",
+                table_id
+            ),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+}
+
+// Auth responses
+pub fn unauthorized_response() -> Response<Body> {
+    GenericResponse {
+        msg: "Missing or invalid API key or token".to_string(),
+        status_code: StatusCode::UNAUTHORIZED.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn forbidden_response(scope: Scope) -> Response<Body> {
+    GenericResponse {
+        msg: format!("Credentials are missing the {} scope", scope.as_str()),
+        status_code: StatusCode::FORBIDDEN.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn invalid_login_response() -> Response<Body> {
+    // Same message for unknown users and wrong passwords so usernames can't be probed
+    GenericResponse {
+        msg: "Invalid username or password".to_string(),
+        status_code: StatusCode::UNAUTHORIZED.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn invalid_refresh_token_response() -> Response<Body> {
+    GenericResponse {
+        msg: "Invalid, expired or revoked refresh token".to_string(),
+        status_code: StatusCode::UNAUTHORIZED.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn logout_response(revoked: u64) -> Response<Body> {
+    GenericResponse {
+        msg: "Logged out".to_string(),
+        status_code: StatusCode::OK.as_u16(),
+        rows: Some(revoked),
+    }
+    .into_response()
+}
+
+pub trait AuthErrorResponseBuilder {
+    fn authenticate_err(&self) -> GenericResponse;
+    fn login_err(&self, username: &str) -> GenericResponse;
+    fn refresh_err(&self) -> GenericResponse;
+    fn logout_err(&self) -> GenericResponse;
+}
+
+impl AuthErrorResponseBuilder for Error {
+    fn authenticate_err(&self) -> GenericResponse {
+        GenericResponse {
+            msg: "Error when attempting to verify credentials".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn login_err(&self, username: &str) -> GenericResponse {
+        GenericResponse {
+            msg: format!("Error when attempting to log in {}", username),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn refresh_err(&self) -> GenericResponse {
+        GenericResponse {
+            msg: "Error when attempting to refresh token".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn logout_err(&self) -> GenericResponse {
+        GenericResponse {
+            msg: "Error when attempting to log out".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+}
+
+// Token and password hashing failures are not database errors but get the same treatment
+pub fn token_err(username: &str) -> GenericResponse {
+    GenericResponse {
+        msg: format!("Error when attempting to issue tokens for {}", username),
+        status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+        rows: None,
+    }
+}
+
+pub trait ApiKeySuccessResponseBuilder {
+    fn revoke_api_key_response(self, key_id: u32) -> Response<Body>;
+}
+
+impl ApiKeySuccessResponseBuilder for MySqlQueryResult {
+    fn revoke_api_key_response(self, key_id: u32) -> Response<Body> {
+        // Same assumptions as deletes, revoking an unknown or revoked key is not a 4xx
+        GenericResponse {
+            msg: format!("API key {} revoked", key_id),
+            status_code: StatusCode::OK.as_u16(),
+            rows: Some(self.rows_affected()),
+        }
+        .into_response()
+    }
+}
+
+pub trait ApiKeyErrorResponseBuilder {
+    fn create_api_key_err(&self, body: CreateApiKeyRequest) -> GenericResponse;
+    fn get_api_keys_err(&self) -> GenericResponse;
+    fn revoke_api_key_err(&self, key_id: u32) -> GenericResponse;
+}
+
+impl ApiKeyErrorResponseBuilder for Error {
+    fn create_api_key_err(&self, body: CreateApiKeyRequest) -> GenericResponse {
+        GenericResponse {
+            msg: format!("Error when attempting to create API key {}", body.name),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn get_api_keys_err(&self) -> GenericResponse {
+        GenericResponse {
+            msg: "Error when attempting to list API keys".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn revoke_api_key_err(&self, key_id: u32) -> GenericResponse {
+        GenericResponse {
+            msg: format!("Error when attempting to revoke API key {}", key_id),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+}
+
+pub trait WebhookSuccessResponseBuilder {
+    fn delete_webhook_response(self, webhook_id: u32) -> Response<Body>;
+    fn retry_delivery_response(self, delivery_id: u64) -> Response<Body>;
+}
+
+impl WebhookSuccessResponseBuilder for MySqlQueryResult {
+    fn delete_webhook_response(self, webhook_id: u32) -> Response<Body> {
+        GenericResponse {
+            msg: format!("Webhook {} deleted", webhook_id),
+            status_code: StatusCode::OK.as_u16(),
+            rows: Some(self.rows_affected()),
+        }
+        .into_response()
+    }
+
+    fn retry_delivery_response(self, delivery_id: u64) -> Response<Body> {
+        // Only dead deliveries are requeued, anything else leaves rows at 0
+        GenericResponse {
+            msg: format!("Webhook delivery {} queued for retry", delivery_id),
+            status_code: StatusCode::OK.as_u16(),
+            rows: Some(self.rows_affected()),
+        }
+        .into_response()
+    }
+}
+
+pub trait WebhookErrorResponseBuilder {
+    fn create_webhook_err(&self, url: &str) -> GenericResponse;
+    fn get_webhooks_err(&self) -> GenericResponse;
+    fn delete_webhook_err(&self, webhook_id: u32) -> GenericResponse;
+    fn get_dead_letters_err(&self) -> GenericResponse;
+    fn retry_delivery_err(&self, delivery_id: u64) -> GenericResponse;
+}
+
+impl WebhookErrorResponseBuilder for Error {
+    fn create_webhook_err(&self, url: &str) -> GenericResponse {
+        GenericResponse {
+            msg: format!("Error when attempting to create webhook for {}", url),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn get_webhooks_err(&self) -> GenericResponse {
+        GenericResponse {
+            msg: "Error when attempting to list webhooks".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn delete_webhook_err(&self, webhook_id: u32) -> GenericResponse {
+        GenericResponse {
+            msg: format!("Error when attempting to delete webhook {}", webhook_id),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn get_dead_letters_err(&self) -> GenericResponse {
+        GenericResponse {
+            msg: "Error when attempting to list dead webhook deliveries".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn retry_delivery_err(&self, delivery_id: u64) -> GenericResponse {
+        GenericResponse {
+            msg: format!(
+                "Error when attempting to retry webhook delivery {}",
+                delivery_id
+            ),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+}
+
+pub fn invalid_webhook_response(reason: String) -> Response<Body> {
+    GenericResponse {
+        msg: reason,
+        status_code: StatusCode::BAD_REQUEST.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn invalid_staff_response(reason: String) -> Response<Body> {
+    GenericResponse {
+        msg: reason,
+        status_code: StatusCode::BAD_REQUEST.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub trait StaffSuccessResponseBuilder {
+    fn create_staff_response(self, username: &str) -> Response<Body>;
+    fn delete_staff_response(self, staff_id: u32) -> Response<Body>;
+}
+
+impl StaffSuccessResponseBuilder for MySqlQueryResult {
+    fn create_staff_response(self, username: &str) -> Response<Body> {
+        GenericResponse {
+            msg: format!(
+                "Sucessfully created staff account {} with id {}",
+                username,
+                self.last_insert_id()
+            ),
+            status_code: StatusCode::OK.as_u16(),
+            rows: Some(self.rows_affected()),
+        }
+        .into_response()
+    }
+
+    fn delete_staff_response(self, staff_id: u32) -> Response<Body> {
+        GenericResponse {
+            msg: format!("Staff account {} deleted", staff_id),
+            status_code: StatusCode::OK.as_u16(),
+            rows: Some(self.rows_affected()),
+        }
+        .into_response()
+    }
+}
+
+pub trait StaffErrorResponseBuilder {
+    fn create_staff_err(&self, username: &str) -> GenericResponse;
+    fn get_staff_err(&self) -> GenericResponse;
+    fn delete_staff_err(&self, staff_id: u32) -> GenericResponse;
+}
+
+impl StaffErrorResponseBuilder for Error {
+    fn create_staff_err(&self, username: &str) -> GenericResponse {
+        // Most likely the username is already taken
+        GenericResponse {
+            msg: format!("Error when attempting to create staff account {}", username),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn get_staff_err(&self) -> GenericResponse {
+        GenericResponse {
+            msg: "Error when attempting to list staff accounts".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+
+    fn delete_staff_err(&self, staff_id: u32) -> GenericResponse {
+        GenericResponse {
+            msg: format!("Error when attempting to delete staff account {}", staff_id),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+}
+
+// Bulk responses
+pub trait ImportSuccessResponseBuilder {
+    fn import_response(self) -> Response<Body>;
+}
+
+impl ImportSuccessResponseBuilder for ImportReport {
+    // Unprocessable when errors kept every row out, which is always the case for a failed all_or_nothing import
+    fn import_response(self) -> Response<Body> {
+        let status_code = if self.failed > 0 && self.imported == 0 {
+            StatusCode::UNPROCESSABLE_ENTITY
+        } else {
+            StatusCode::OK
+        };
+        (status_code, Json(self)).into_response()
+    }
+}
+
+pub trait BulkErrorResponseBuilder {
+    fn import_err(&self, resource: &str) -> GenericResponse;
+}
+
+impl BulkErrorResponseBuilder for Error {
+    fn import_err(&self, resource: &str) -> GenericResponse {
+        GenericResponse {
+            msg: format!("Error when attempting to import {}", resource),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+}
+
+// Report responses
+pub trait ReportErrorResponseBuilder {
+    fn get_report_err(&self, group_by: &str) -> GenericResponse;
+}
+
+impl ReportErrorResponseBuilder for Error {
+    fn get_report_err(&self, group_by: &str) -> GenericResponse {
+        GenericResponse {
+            msg: format!("Error when attempting to build the {} report", group_by),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            rows: None,
+        }
+    }
+}
+
+pub fn invalid_report_response(reason: String) -> Response<Body> {
+    GenericResponse {
+        msg: reason,
+        status_code: StatusCode::BAD_REQUEST.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+// Limit responses
+pub fn too_many_requests_response(retry_after_secs: u64) -> Response<Body> {
+    GenericResponse {
+        msg: format!(
+            "Too many requests, retry after {} second(s)",
+            retry_after_secs
+        ),
+        status_code: StatusCode::TOO_MANY_REQUESTS.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn too_many_items_response(max_items: usize) -> Response<Body> {
+    GenericResponse {
+        msg: format!("Can not add more than {} item(s) per request", max_items),
+        status_code: StatusCode::PAYLOAD_TOO_LARGE.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn invalid_subscription_response(reason: String) -> Response<Body> {
+    GenericResponse {
+        msg: reason,
+        status_code: StatusCode::BAD_REQUEST.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn invalid_idempotency_key_response(max_length: usize) -> Response<Body> {
+    GenericResponse {
+        msg: format!(
+            "Idempotency-Key must be between 1 and {} visible ASCII characters",
+            max_length
+        ),
+        status_code: StatusCode::BAD_REQUEST.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn idempotency_in_progress_response() -> Response<Body> {
+    GenericResponse {
+        msg: "A request with this Idempotency-Key is still being processed".to_string(),
+        status_code: StatusCode::CONFLICT.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn idempotency_conflict_response() -> Response<Body> {
+    GenericResponse {
+        msg: "Idempotency-Key was already used for a different request".to_string(),
+        status_code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
+
+pub fn payload_too_large_response(max_bytes: usize) -> Response<Body> {
+    GenericResponse {
+        msg: format!("Request body is larger than {} bytes", max_bytes),
+        status_code: StatusCode::PAYLOAD_TOO_LARGE.as_u16(),
+        rows: None,
+    }
+    .into_response()
+}
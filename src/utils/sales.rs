@@ -0,0 +1,44 @@
+use sqlx::mysql::MySqlConnection;
+use sqlx::QueryBuilder;
+
+use crate::models::database::Items;
+
+// Six binds per row, well under MySQL's limit of 65535 placeholders per statement
+const MAX_SALES_PER_INSERT: usize = 1_000;
+
+// Served items are deleted, so every delete copies the items into item_sales first and reports
+// keep counting them. Called inside the transaction that deletes the items.
+pub async fn record_sales(conn: &mut MySqlConnection, items: &[Items]) -> Result<(), sqlx::Error> {
+    for chunk in items.chunks(MAX_SALES_PER_INSERT) {
+        QueryBuilder::new(
+            "INSERT INTO item_sales (item_id, table_id, item, cook_time, customer_id, created_at) ",
+        )
+        .push_values(chunk, |mut builder, item| {
+            builder
+                .push_bind(item.id)
+                .push_bind(item.table_id)
+                .push_bind(&item.item)
+                .push_bind(item.cook_time)
+                .push_bind(&item.customer_id)
+                .push_bind(item.created_at);
+        })
+        .build()
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+// An undone delete puts the items back in `items`, so they must not be counted as sold too
+pub async fn cancel_sales(conn: &mut MySqlConnection, items: &[Items]) -> Result<(), sqlx::Error> {
+    for chunk in items.chunks(MAX_SALES_PER_INSERT) {
+        let mut delete = QueryBuilder::new("DELETE FROM item_sales WHERE item_id IN (");
+        let mut ids = delete.separated(", ");
+        for item in chunk {
+            ids.push_bind(item.id);
+        }
+        ids.push_unseparated(")");
+        delete.build().execute(&mut *conn).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::io;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::signal;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+// Counts requests currently being handled so shutdown can report what it drained or dropped.
+// Event streams count until they end, which they do as soon as shutdown starts.
+#[derive(Default)]
+pub struct InFlight {
+    active: AtomicUsize,
+    total: AtomicU64,
+    shutdown: CancellationToken,
+}
+
+impl InFlight {
+    // Held for as long as the request or stream is being served
+    pub fn enter(self: &Arc<Self>) -> InFlightGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        self.total.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.clone())
+    }
+
+    // Tells the SSE and websocket streams to end so the drain doesn't wait on them
+    pub fn start_shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::SeqCst)
+    }
+}
+
+// Decrements on drop so requests whose futures are cancelled are not counted forever
+pub struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub async fn track_in_flight(
+    State(in_flight): State<Arc<InFlight>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let _guard = in_flight.enter();
+    next.run(request).await
+}
+
+// What the drain got done, logged once shutdown completes
+#[derive(Debug)]
+pub struct DrainSummary {
+    // Whether every request finished within the timeout
+    pub drained: bool,
+    pub in_flight_at_signal: usize,
+    // Still running when the timeout hit
+    pub abandoned: usize,
+    pub requests_served: u64,
+    pub drain_ms: u64,
+}
+
+// Waits for the server task to finish what it is serving once it stopped accepting, and aborts
+// it when that takes longer than `timeout`
+pub async fn drain(
+    server: &mut JoinHandle<io::Result<()>>,
+    in_flight: &InFlight,
+    timeout: Duration,
+) -> DrainSummary {
+    let in_flight_at_signal = in_flight.active();
+    let drain_start = Instant::now();
+    info!(
+        in_flight = in_flight_at_signal,
+        timeout_secs = timeout.as_secs(),
+        "Draining in-flight requests"
+    );
+    let drained = match tokio::time::timeout(timeout, &mut *server).await {
+        Ok(result) => {
+            result.unwrap().unwrap();
+            true
+        }
+        Err(_) => {
+            server.abort();
+            false
+        }
+    };
+
+    DrainSummary {
+        drained,
+        in_flight_at_signal,
+        abandoned: in_flight.active(),
+        requests_served: in_flight.total(),
+        drain_ms: drain_start.elapsed().as_millis() as u64,
+    }
+}
+
+// Resolves on ctrl-c, or SIGTERM on unix (what docker and most process managers send)
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Failed to install ctrl-c handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!(signal = "ctrl_c", "Shutdown signal received"),
+        _ = terminate => info!(signal = "sigterm", "Shutdown signal received"),
+    }
+}
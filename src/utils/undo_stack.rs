@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::database::{Items, Table};
+use crate::models::response::DomainEvent;
+
+// Cap on how many operations we remember per table so a busy table does not grow unbounded
+const MAX_UNDO_DEPTH: usize = 50;
+
+// A snapshot of the rows removed by a single destructive operation
+#[derive(Debug, Clone)]
+pub enum UndoEntry {
+    Items(Vec<Items>),
+    Table { table: Table, items: Vec<Items> },
+}
+
+impl UndoEntry {
+    // The rows as they are written back. Item versions move on so ETags read before the delete
+    // no longer match the restored rows, the table draws its version when it is inserted.
+    pub fn restored(&self) -> UndoEntry {
+        let restore_items = |items: &Vec<Items>| {
+            items
+                .iter()
+                .map(|item| Items {
+                    version: item.version + 1,
+                    ..item.clone()
+                })
+                .collect()
+        };
+        match self {
+            UndoEntry::Items(items) => UndoEntry::Items(restore_items(items)),
+            UndoEntry::Table { table, items } => UndoEntry::Table {
+                table: table.clone(),
+                items: restore_items(items),
+            },
+        }
+    }
+
+    // What subscribers are told when the rows are deleted. Items deleted with their table went
+    // through the cascade.
+    pub fn deleted_events(&self) -> Vec<DomainEvent> {
+        match self {
+            UndoEntry::Items(items) => DomainEvent::items_deleted(items),
+            UndoEntry::Table { table, items } => {
+                let mut events = DomainEvent::items_deleted(items);
+                events.push(DomainEvent::TableDeleted { table_id: table.id });
+                events
+            }
+        }
+    }
+
+    // Restored rows look the same as newly added ones to subscribers
+    pub fn added_events(&self) -> Vec<DomainEvent> {
+        match self {
+            UndoEntry::Items(items) => DomainEvent::items_added(items),
+            UndoEntry::Table { table, items } => {
+                let mut events = vec![DomainEvent::TableAdded {
+                    table: table.clone(),
+                }];
+                events.extend(DomainEvent::items_added(items));
+                events
+            }
+        }
+    }
+}
+
+pub struct UndoRecord {
+    pub deleted_at: Instant,
+    pub entry: UndoEntry,
+}
+
+// In-memory per-table stack of deleted rows. Entries older than the window are dropped lazily.
+pub struct UndoStack {
+    window: Duration,
+    stacks: Mutex<HashMap<u32, Vec<UndoRecord>>>,
+}
+
+impl UndoStack {
+    pub fn new(window: Duration) -> Self {
+        UndoStack {
+            window,
+            stacks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn push(&self, table_id: u32, entry: UndoEntry) {
+        let mut stacks = self.stacks.lock().unwrap();
+        let stack = stacks.entry(table_id).or_default();
+        stack.retain(|record| record.deleted_at.elapsed() <= self.window);
+        if stack.len() >= MAX_UNDO_DEPTH {
+            stack.remove(0);
+        }
+        stack.push(UndoRecord {
+            deleted_at: Instant::now(),
+            entry,
+        });
+    }
+
+    // Puts back a record that failed to restore. It keeps its deletion time so retries don't
+    // stretch the undo window, and goes below anything deleted since it was popped.
+    pub fn push_back(&self, table_id: u32, record: UndoRecord) {
+        if record.deleted_at.elapsed() > self.window {
+            return;
+        }
+        let mut stacks = self.stacks.lock().unwrap();
+        let stack = stacks.entry(table_id).or_default();
+        let position = stack
+            .iter()
+            .position(|newer| newer.deleted_at > record.deleted_at)
+            .unwrap_or(stack.len());
+        stack.insert(position, record);
+        if stack.len() > MAX_UNDO_DEPTH {
+            stack.remove(0);
+        }
+    }
+
+    // Groups deleted items by table so each table gets its own undo entry
+    pub fn push_items(&self, deleted: Vec<Items>) {
+        let mut by_table: HashMap<u32, Vec<Items>> = HashMap::new();
+        for item in deleted {
+            by_table.entry(item.table_id).or_default().push(item);
+        }
+        for (table_id, items) in by_table {
+            self.push(table_id, UndoEntry::Items(items));
+        }
+    }
+
+    // Pops the most recent operation for a table that is still within the undo window
+    pub fn pop(&self, table_id: u32) -> Option<UndoRecord> {
+        let mut stacks = self.stacks.lock().unwrap();
+        let stack = stacks.get_mut(&table_id)?;
+        stack.retain(|record| record.deleted_at.elapsed() <= self.window);
+        let record = stack.pop();
+        if stack.is_empty() {
+            stacks.remove(&table_id);
+        }
+        record
+    }
+}
@@ -0,0 +1,302 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::mysql::{MySqlConnection, MySqlPool};
+use sqlx::QueryBuilder;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast::error::RecvError, Notify};
+use tracing::{debug, error, info, warn};
+
+use crate::models::response::{DomainEvent, EventMessage};
+use crate::utils::config::WebhooksConfig;
+use crate::utils::database_connection::AppDatabase;
+use crate::utils::events::EventBus;
+
+// Sent with every delivery. The id stays the same across retries so receivers can deduplicate.
+pub const WEBHOOK_ID_HEADER: &str = "x-webhook-id";
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+// "sha256=" followed by the hex HMAC-SHA256 of "{timestamp}.{body}" keyed with the subscription secret
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", signature)
+}
+
+// Three binds per row, well under MySQL's limit of 65535 placeholders per statement
+const MAX_DELIVERIES_PER_INSERT: usize = 1_000;
+
+// Comma separated event types as stored in webhook_subscriptions.events, empty for every event
+pub fn subscribed_to(events: &str, event_type: &str) -> bool {
+    events.is_empty() || events.split(',').any(|event| event == event_type)
+}
+
+// Queues a delivery of every event for each subscription that wants it. Handlers call this
+// inside the transaction that makes the change, so the deliveries are committed with it and a
+// crash right after the commit can't lose them.
+pub async fn enqueue_deliveries(
+    conn: &mut MySqlConnection,
+    events: &[DomainEvent],
+) -> Result<(), sqlx::Error> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let subscriptions: Vec<(u32, String)> =
+        sqlx::query_as("SELECT id, events FROM webhook_subscriptions")
+            .fetch_all(&mut *conn)
+            .await?;
+
+    let mut deliveries = Vec::new();
+    for event in events {
+        let event_type = event.event_type();
+        let wanted_by: Vec<u32> = subscriptions
+            .iter()
+            .filter(|(_, wanted)| subscribed_to(wanted, event_type))
+            .map(|(id, _)| *id)
+            .collect();
+        if wanted_by.is_empty() {
+            continue;
+        }
+        let payload = serde_json::to_string(event).expect("Events always serialize");
+        for subscription_id in wanted_by {
+            deliveries.push((subscription_id, event_type, payload.clone()));
+        }
+    }
+
+    for chunk in deliveries.chunks(MAX_DELIVERIES_PER_INSERT) {
+        QueryBuilder::new("INSERT INTO webhook_outbox (subscription_id, event_type, payload) ")
+            .push_values(
+                chunk,
+                |mut builder, (subscription_id, event_type, payload)| {
+                    builder
+                        .push_bind(*subscription_id)
+                        .push_bind(*event_type)
+                        .push_bind(payload);
+                },
+            )
+            .build()
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct DueDelivery {
+    id: u64,
+    payload: String,
+    attempts: u32,
+    created_at: DateTime<Utc>,
+    url: String,
+    secret: String,
+}
+
+impl DueDelivery {
+    // The event message the streams use, with the delivery id as its id. Rows queued before the
+    // payload held only the event still parse, serde skips their id and at.
+    fn body(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&EventMessage {
+            id: self.id,
+            at: self.created_at,
+            event: serde_json::from_str(&self.payload)?,
+        })
+    }
+}
+
+// Delivers the outbox rows handlers queue with enqueue_deliveries. Rows stay in the outbox
+// until delivered or dead, so failed deliveries survive restarts.
+pub struct Webhooks {
+    config: WebhooksConfig,
+    client: reqwest::Client,
+    wake: Notify,
+}
+
+impl Webhooks {
+    pub fn new(config: WebhooksConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.request_timeout_secs))
+            .build()
+            .expect("Failed to build webhook HTTP client");
+        Webhooks {
+            config,
+            client,
+            wake: Notify::new(),
+        }
+    }
+
+    // Checks the outbox straight away instead of waiting for the next poll
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    pub fn spawn(self: Arc<Self>, app_database: Arc<AppDatabase>, event_bus: &EventBus) {
+        // Events only wake the worker early, what gets delivered is read from the outbox.
+        // A lagged receiver still means there is work, the poll picks up anything else.
+        let mut events = event_bus.subscribe();
+        let webhooks = self.clone();
+        tokio::spawn(async move {
+            while let Ok(_) | Err(RecvError::Lagged(_)) = events.recv().await {
+                webhooks.wake();
+            }
+        });
+
+        tokio::spawn(async move {
+            let poll_interval = Duration::from_millis(self.config.poll_interval_ms);
+            loop {
+                if app_database.is_connected() {
+                    if let Err(err) = self.deliver_due(&app_database).await {
+                        error!(error = %err, "Failed to read due webhook deliveries");
+                    }
+                }
+                tokio::select! {
+                    _ = self.wake.notified() => {}
+                    _ = tokio::time::sleep(poll_interval) => {}
+                }
+            }
+        });
+    }
+
+    async fn deliver_due(&self, app_database: &AppDatabase) -> Result<(), sqlx::Error> {
+        let pool = &app_database.connection_pool;
+        let due = self.claim_due(pool).await?;
+
+        for delivery in due {
+            let attempts = delivery.attempts + 1;
+            match self.send(&delivery).await {
+                Ok(()) => {
+                    debug!(delivery_id = delivery.id, attempts, "Webhook delivered");
+                    sqlx::query(
+                        "UPDATE webhook_outbox SET status = 'delivered', attempts = ?, \
+                         delivered_at = CURRENT_TIMESTAMP, last_error = NULL WHERE id = ?",
+                    )
+                    .bind(attempts)
+                    .bind(delivery.id)
+                    .execute(pool)
+                    .await?;
+                }
+
+                Err((status_code, reason)) if attempts >= self.config.max_attempts => {
+                    warn!(delivery_id = delivery.id, attempts, error = %reason, "Webhook moved to the dead letter list");
+                    sqlx::query(
+                        "UPDATE webhook_outbox SET status = 'dead', attempts = ?, \
+                         last_status_code = ?, last_error = ? WHERE id = ?",
+                    )
+                    .bind(attempts)
+                    .bind(status_code)
+                    .bind(truncate(&reason))
+                    .bind(delivery.id)
+                    .execute(pool)
+                    .await?;
+                }
+
+                Err((status_code, reason)) => {
+                    let backoff = self.backoff_secs(attempts);
+                    info!(delivery_id = delivery.id, attempts, retry_in_secs = backoff, error = %reason, "Webhook delivery failed");
+                    sqlx::query(
+                        "UPDATE webhook_outbox SET attempts = ?, last_status_code = ?, last_error = ?, \
+                         next_attempt_at = CURRENT_TIMESTAMP + INTERVAL ? SECOND WHERE id = ?",
+                    )
+                    .bind(attempts)
+                    .bind(status_code)
+                    .bind(truncate(&reason))
+                    .bind(backoff)
+                    .bind(delivery.id)
+                    .execute(pool)
+                    .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Pushes next_attempt_at of the due rows past the time the batch can take to send, so other
+    // instances skip them. Rows locked by another instance's claim are skipped too. If this
+    // instance dies mid-batch, its rows become due again once the claim runs out.
+    async fn claim_due(&self, pool: &MySqlPool) -> Result<Vec<DueDelivery>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let due: Vec<DueDelivery> = sqlx::query_as(
+            "SELECT o.id, o.payload, o.attempts, o.created_at, s.url, s.secret FROM webhook_outbox o \
+             JOIN webhook_subscriptions s ON s.id = o.subscription_id \
+             WHERE o.status = 'pending' AND o.next_attempt_at <= CURRENT_TIMESTAMP \
+             ORDER BY o.id LIMIT ? FOR UPDATE OF o SKIP LOCKED",
+        )
+        .bind(self.config.batch_size)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if !due.is_empty() {
+            let claim_secs = self
+                .config
+                .request_timeout_secs
+                .saturating_mul(due.len() as u64 + 1);
+            let mut query = QueryBuilder::new(
+                "UPDATE webhook_outbox SET next_attempt_at = CURRENT_TIMESTAMP + INTERVAL ",
+            );
+            query.push_bind(claim_secs).push(" SECOND WHERE id IN (");
+            let mut ids = query.separated(", ");
+            for delivery in &due {
+                ids.push_bind(delivery.id);
+            }
+            query.push(")").build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(due)
+    }
+
+    // Any 2xx counts as delivered, everything else is retried
+    async fn send(&self, delivery: &DueDelivery) -> Result<(), (Option<u16>, String)> {
+        let body = delivery
+            .body()
+            .map_err(|err| (None, format!("Invalid payload: {}", err)))?;
+        let timestamp = chrono::Utc::now().timestamp();
+        let response = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_ID_HEADER, delivery.id.to_string())
+            .header(WEBHOOK_TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                WEBHOOK_SIGNATURE_HEADER,
+                sign_payload(&delivery.secret, timestamp, &body),
+            )
+            .body(body)
+            .send()
+            .await
+            .map_err(|err| (None, err.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err((
+                Some(status.as_u16()),
+                format!("Receiver responded with {}", status),
+            ))
+        }
+    }
+
+    // initial_backoff_secs doubled for every failed attempt, capped at max_backoff_secs
+    fn backoff_secs(&self, attempts: u32) -> u64 {
+        let factor = 2u64.saturating_pow(attempts.saturating_sub(1));
+        self.config
+            .initial_backoff_secs
+            .saturating_mul(factor)
+            .min(self.config.max_backoff_secs)
+    }
+}
+
+// last_error is a VARCHAR(1024)
+fn truncate(reason: &str) -> String {
+    reason.chars().take(1024).collect()
+}
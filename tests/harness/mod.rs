@@ -0,0 +1,254 @@
+// Every test gets its own database on the MySQL server at TEST_DATABASE_URL and its own copy of
+// the API serving on an ephemeral port, so tests can run in parallel without a running server.
+use chrono::{DateTime, Utc};
+use dotenv::dotenv;
+use metrics_exporter_prometheus::PrometheusHandle;
+use rand::Rng;
+use sqlx::mysql::{MySqlConnection, MySqlPool};
+use sqlx::{Connection, Executor};
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+
+use restaurant_api::build_router;
+use restaurant_api::utils::app_state::AppState;
+use restaurant_api::utils::config::Config;
+use restaurant_api::utils::database_connection::{database_connect, wait_for_database};
+use restaurant_api::utils::metrics::install_metrics_recorder;
+use restaurant_api::utils::migrations::{execute_script, migrate};
+use restaurant_api_client::models::database::{Items, Table};
+use restaurant_api_client::Client;
+
+pub const ADMIN_API_KEY: &str = "test-admin-key";
+
+// The recorder is global, so every app in the test binary reports into the same one
+static METRICS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+pub struct TestApp {
+    pub base_url: String,
+    pub config: Config,
+    pool: MySqlPool,
+    server_url: String,
+    database: String,
+}
+
+impl TestApp {
+    pub async fn spawn() -> TestApp {
+        TestApp::spawn_with(|_| {}).await
+    }
+
+    // `configure` runs on the test config before the app is built
+    pub async fn spawn_with(configure: impl FnOnce(&mut Config)) -> TestApp {
+        TestApp::spawn_inner(configure, None).await
+    }
+
+    // Starts on a database that already holds `schema`, e.g. one from an older version, and
+    // migrates it like any other
+    pub async fn spawn_on_schema(schema: &str) -> TestApp {
+        TestApp::spawn_inner(|_| {}, Some(schema)).await
+    }
+
+    async fn spawn_inner(configure: impl FnOnce(&mut Config), schema: Option<&str>) -> TestApp {
+        dotenv().ok();
+        let server_url =
+            std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL env var not set!");
+        let server_url = server_url.trim_end_matches('/').to_string();
+        let database = format!("restaurant_test_{:016x}", rand::thread_rng().gen::<u64>());
+        create_database(&server_url, &database).await;
+
+        let mut config = Config::default();
+        config.database.url = format!("{}/{}", server_url, database);
+        // Tests run in parallel, keep each app's share of the server's connections small
+        config.database.max_connections = 4;
+        config.auth.admin_api_key = Some(ADMIN_API_KEY.to_string());
+        config.auth.jwt_secret = "test-jwt-secret".to_string();
+        // Failing webhook deliveries are dead lettered within a few seconds
+        config.webhooks.poll_interval_ms = 200;
+        config.webhooks.max_attempts = 3;
+        config.webhooks.initial_backoff_secs = 1;
+        configure(&mut config);
+
+        let app_database = database_connect(&config.database).unwrap();
+        let metrics_handle = METRICS_HANDLE.get_or_init(install_metrics_recorder).clone();
+        let app_state = AppState::new(&config, app_database, metrics_handle);
+        wait_for_database(&app_state.app_database, &config.database)
+            .await
+            .expect("Test database is not reachable");
+        if let Some(schema) = schema {
+            execute_script(&app_state.app_database.connection_pool, schema)
+                .await
+                .expect("Failed to load the starting schema");
+        }
+        migrate(&app_state.app_database.connection_pool)
+            .await
+            .expect("Failed to create the schema");
+        if config.features.webhooks {
+            app_state
+                .webhooks
+                .clone()
+                .spawn(app_state.app_database.clone(), &app_state.event_bus);
+        }
+        let pool = app_state.app_database.connection_pool.clone();
+
+        let app = build_router(app_state, &config);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+
+        TestApp {
+            base_url,
+            config,
+            pool,
+            server_url,
+            database,
+        }
+    }
+
+    // Client with the bootstrap admin key
+    pub fn client(&self) -> Client {
+        self.anonymous().with_api_key(ADMIN_API_KEY)
+    }
+
+    pub fn anonymous(&self) -> Client {
+        Client::new(&self.base_url)
+    }
+
+    pub fn table(&self, id: u32) -> TableFixture<'_> {
+        TableFixture {
+            app: self,
+            id,
+            seats: 1,
+        }
+    }
+
+    pub fn item(&self, table_id: u32, item: &str) -> ItemFixture<'_> {
+        ItemFixture {
+            app: self,
+            table_id,
+            item: item.to_string(),
+            cook_time: 10,
+            customer_id: None,
+            created_at: None,
+        }
+    }
+}
+
+impl Drop for TestApp {
+    // Dropping happens outside of any async context, so the database is dropped on a thread of
+    // its own. The server task goes away with the test's runtime.
+    fn drop(&mut self) {
+        let server_url = self.server_url.clone();
+        let database = self.database.clone();
+        let dropped = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    let mut connection = MySqlConnection::connect(&server_url).await?;
+                    connection
+                        .execute(format!("DROP DATABASE IF EXISTS `{}`", database).as_str())
+                        .await
+                        .map(|_| ())
+                })
+        })
+        .join();
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("Failed to drop test database {}", self.database);
+        }
+    }
+}
+
+// Inserted straight into the database, fixtures publish no events and skip the undo stack
+pub struct TableFixture<'a> {
+    app: &'a TestApp,
+    id: u32,
+    seats: u32,
+}
+
+impl TableFixture<'_> {
+    pub fn seats(mut self, seats: u32) -> Self {
+        self.seats = seats;
+        self
+    }
+
+    pub async fn create(self) -> Table {
+        sqlx::query("INSERT INTO tables (id, seats) VALUES (?, ?)")
+            .bind(self.id)
+            .bind(self.seats)
+            .execute(&self.app.pool)
+            .await
+            .expect("Failed to insert table fixture");
+        Table {
+            id: self.id,
+            seats: self.seats,
+            version: 1,
+        }
+    }
+}
+
+pub struct ItemFixture<'a> {
+    app: &'a TestApp,
+    table_id: u32,
+    item: String,
+    cook_time: u8,
+    customer_id: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+impl ItemFixture<'_> {
+    pub fn cook_time(mut self, cook_time: u8) -> Self {
+        self.cook_time = cook_time;
+        self
+    }
+
+    pub fn customer(mut self, customer_id: &str) -> Self {
+        self.customer_id = Some(customer_id.to_string());
+        self
+    }
+
+    // Defaults to now
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    pub async fn create(self) -> Items {
+        let result = sqlx::query(
+            "INSERT INTO items (table_id, item, cook_time, customer_id, created_at) VALUES (?, ?, ?, ?, COALESCE(?, CURRENT_TIMESTAMP))",
+        )
+        .bind(self.table_id)
+        .bind(&self.item)
+        .bind(self.cook_time)
+        .bind(&self.customer_id)
+        .bind(self.created_at)
+        .execute(&self.app.pool)
+        .await
+        .expect("Failed to insert item fixture");
+
+        sqlx::query_as::<_, Items>(
+            "SELECT id, table_id, item, cook_time, customer_id, created_at, version FROM items WHERE id = ?",
+        )
+        .bind(result.last_insert_id() as u32)
+        .fetch_one(&self.app.pool)
+        .await
+        .expect("Failed to read back item fixture")
+    }
+}
+
+// Creates an empty database, the app fills in the schema once it is connected
+async fn create_database(server_url: &str, database: &str) {
+    let mut connection = MySqlConnection::connect(server_url)
+        .await
+        .expect("Failed to connect to TEST_DATABASE_URL");
+    connection
+        .execute(format!("CREATE DATABASE `{}`", database).as_str())
+        .await
+        .expect("Failed to create test database");
+}
@@ -0,0 +1,429 @@
+use axum::body::{to_bytes, Body};
+use axum::extract::ConnectInfo;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use rstest::rstest;
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tower::ServiceExt;
+
+use restaurant_api::models::response::{GenericResponse, ReadinessResponse};
+use restaurant_api::utils::app_state::AppState;
+use restaurant_api::utils::config::Config;
+use restaurant_api::utils::database_connection::{database_connect, wait_for_database};
+use restaurant_api::utils::shutdown::drain;
+use restaurant_api::{build_router, route_table};
+use restaurant_api_client::{Client, RetryPolicy};
+
+// Drives the router in-process, no server or database needed. The pool is never connected,
+// so only routes that answer without the database are covered here.
+#[rstest]
+#[case("/health/live", true, StatusCode::OK)] // Liveness never touches the database
+#[case("/table/1", true, StatusCode::UNAUTHORIZED)] // Protected routes need credentials
+#[case("/openapi.json", true, StatusCode::OK)] // Docs are on by default
+#[case("/openapi.json", false, StatusCode::NOT_FOUND)] // Feature toggles decide the routes
+#[case("/docs/swagger-ui.css", true, StatusCode::OK)] // Swagger UI is served from the binary
+#[case("/docs/missing.js", true, StatusCode::NOT_FOUND)] // Only the vendored assets
+#[tokio::test]
+async fn test_router_oneshot(
+    #[case] uri: &str,
+    #[case] docs: bool,
+    #[case] expected_status: StatusCode,
+) {
+    let mut config = Config::default();
+    config.features.docs = docs;
+
+    // Rate limiting reads the client address that axum::serve would normally insert
+    let request = Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::empty())
+        .unwrap();
+    let response = test_router(&config).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), expected_status);
+    assert!(response.headers().contains_key("x-request-id"));
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    println!(
+        "\n=> Route: {}\n=> Response: {}\n",
+        uri,
+        String::from_utf8_lossy(&body)
+    );
+}
+
+// The server is up before the database, readiness holds traffic off until it connects
+#[rstest]
+#[tokio::test]
+async fn test_readiness_before_connect() {
+    let config = Config::default();
+    let request = Request::builder()
+        .uri("/health/ready")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::empty())
+        .unwrap();
+    let response = test_router(&config).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let readiness: ReadinessResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(readiness.status, "unavailable");
+    assert_eq!(readiness.components.len(), 2);
+    for component in readiness.components {
+        assert_eq!(component.status, "down");
+        assert_eq!(
+            component.detail.as_deref(),
+            Some("Waiting for the initial database connection")
+        );
+    }
+}
+
+// Startup keeps retrying an unreachable database and gives up once DB_CONNECT_MAX_WAIT_SECS is up
+#[rstest]
+#[tokio::test]
+async fn test_wait_for_database_gives_up() {
+    let mut config = Config::default();
+    config.database.url = format!("mysql://root@{}/restaurant", unreachable_addr());
+    config.database.acquire_timeout_secs = 1;
+    config.database.connect_initial_backoff_ms = 50;
+    config.database.connect_max_backoff_ms = 200;
+    config.database.connect_max_wait_secs = 1;
+    let app_state = test_app_state(&config);
+
+    let started = Instant::now();
+    let result = wait_for_database(&app_state.app_database, &config.database).await;
+    let elapsed = started.elapsed();
+    assert!(result.is_err());
+    assert!(!app_state.app_database.is_connected());
+    // Retried for about the max wait rather than failing at once or waiting forever
+    assert!(
+        elapsed >= Duration::from_millis(500) && elapsed < Duration::from_secs(5),
+        "gave up after {:?}",
+        elapsed
+    );
+}
+
+// Shutdown waits for requests still running up to the drain timeout and reports what it dropped.
+// The database accepts connections but never answers, so a query holds its request open.
+#[rstest]
+#[case(false, true, 0)] // Nothing running, the drain finishes straight away
+#[case(true, false, 1)] // A stuck request is abandoned once the timeout is up
+#[tokio::test]
+async fn test_drain_summary(
+    #[case] stuck_request: bool,
+    #[case] expected_drained: bool,
+    #[case] expected_abandoned: usize,
+) {
+    let stalled_database = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut config = Config::default();
+    config.auth.admin_api_key = Some("router-admin-key".to_string());
+    config.database.url = format!(
+        "mysql://root@{}/restaurant",
+        stalled_database.local_addr().unwrap()
+    );
+    config.database.acquire_timeout_secs = 30;
+    let app_state = test_app_state(&config);
+    let in_flight = app_state.in_flight.clone();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let shutdown = Arc::new(Notify::new());
+    let server = axum::serve(
+        listener,
+        build_router(app_state, &config).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown({
+        let shutdown = shutdown.clone();
+        async move { shutdown.notified().await }
+    });
+    let mut server = tokio::spawn(async move { server.await });
+
+    let client = Client::new(&base_url)
+        .with_api_key("router-admin-key")
+        .with_retry(RetryPolicy::none());
+    client.liveness().await.unwrap();
+    let stuck = stuck_request.then(|| {
+        let client = client.clone();
+        tokio::spawn(async move { client.get_staff().await })
+    });
+    let started = Instant::now();
+    while in_flight.active() < usize::from(stuck_request) {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    shutdown.notify_one();
+    in_flight.start_shutdown();
+    let summary = drain(&mut server, &in_flight, Duration::from_millis(500)).await;
+    assert_eq!(summary.drained, expected_drained);
+    assert_eq!(summary.in_flight_at_signal, usize::from(stuck_request));
+    assert_eq!(summary.abandoned, expected_abandoned);
+    assert_eq!(summary.requests_served, 1 + u64::from(stuck_request));
+    if let Some(stuck) = stuck {
+        assert!(summary.drain_ms >= 500);
+        stuck.abort();
+    }
+}
+
+// The admin tool only checks the database settings, the server needs the rest as well
+#[rstest]
+#[case("mysql://root@localhost:3306/restaurant", true)] // No JWT_SECRET, fine for the admin tool
+#[case("", false)] // Both need a database
+#[test]
+fn test_validate_database_only(#[case] database_url: &str, #[case] cli_valid: bool) {
+    let mut config = Config::default();
+    config.database.url = database_url.to_string();
+    assert_eq!(config.validate_database().is_ok(), cli_valid);
+
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("JWT_SECRET"));
+}
+
+// The scrape answers with the pool gauges while the database is unreachable, the business
+// gauges give up after their timeout instead of waiting out the pool's acquire timeout
+#[rstest]
+#[tokio::test]
+async fn test_metrics_without_database() {
+    let request = Request::builder()
+        .uri("/metrics")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::empty())
+        .unwrap();
+    let response = tokio::time::timeout(
+        Duration::from_secs(10),
+        test_router(&Config::default()).oneshot(request),
+    )
+    .await
+    .expect("Scrape waited on the database")
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains("db_pool_max_connections"));
+    assert!(!body.contains("restaurant_kitchen_queue_length"));
+}
+
+// Event streams count as in flight and end once shutdown starts, so the drain doesn't wait on them
+#[rstest]
+#[tokio::test]
+async fn test_sse_ends_on_shutdown() {
+    let mut config = Config::default();
+    config.auth.admin_api_key = Some("router-admin-key".to_string());
+    let app_state = test_app_state(&config);
+    let in_flight = app_state.in_flight.clone();
+
+    let request = Request::builder()
+        .uri("/events/sse")
+        .header("x-api-key", "router-admin-key")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::empty())
+        .unwrap();
+    let response = build_router(app_state, &config)
+        .oneshot(request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(in_flight.active(), 1);
+
+    in_flight.start_shutdown();
+    // The body only completes once the stream has ended
+    let body = tokio::time::timeout(
+        Duration::from_secs(5),
+        to_bytes(response.into_body(), usize::MAX),
+    )
+    .await;
+    assert!(body.is_ok(), "SSE stream kept going after shutdown");
+    assert_eq!(in_flight.active(), 0);
+}
+
+// Staff accounts are checked before anything is hashed or written
+#[rstest]
+#[case("", "hunter22", "Username is empty")]
+#[case("   ", "hunter22", "Username is empty")]
+#[case(&"a".repeat(91), "hunter22", "Username is longer than 90 characters")]
+#[case("new_cook", "", "Password is shorter than 8 characters")]
+#[case("new_cook", "hunter2", "Password is shorter than 8 characters")]
+#[tokio::test]
+async fn test_create_staff_invalid(
+    #[case] username: &str,
+    #[case] password: &str,
+    #[case] expected_msg: &str,
+) {
+    let mut config = Config::default();
+    config.auth.admin_api_key = Some("router-admin-key".to_string());
+    let body = serde_json::json!({ "username": username, "password": password, "role": "cook" });
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri("/admin/staff/add")
+        .header("x-api-key", "router-admin-key")
+        .header(CONTENT_TYPE, "application/json")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = test_router(&config).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json_resp: GenericResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json_resp.status_code, 400);
+    assert_eq!(json_resp.msg, expected_msg);
+}
+
+// Once a client's bucket is drained it gets a 429 with Retry-After in the usual envelope
+#[rstest]
+#[tokio::test]
+async fn test_rate_limit_exceeded() {
+    let mut config = Config::default();
+    config.limits.rate_limit_ip_burst = 2.0;
+    config.limits.rate_limit_ip_per_sec = 0.1;
+    let app = test_router(&config);
+
+    let mut statuses = Vec::new();
+    let mut response = None;
+    for _ in 0..3 {
+        let request = Request::builder()
+            .uri("/health/live")
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(request).await.unwrap();
+        statuses.push(resp.status());
+        response = Some(resp);
+    }
+    assert_eq!(
+        statuses,
+        vec![
+            StatusCode::OK,
+            StatusCode::OK,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+
+    // One token every 10 seconds
+    let response = response.unwrap();
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=10).contains(&retry_after));
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json_resp: GenericResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json_resp.status_code, 429);
+    assert!(json_resp.rows.is_none());
+
+    // Other clients have their own bucket
+    let request = Request::builder()
+        .uri("/health/live")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 2], 4000))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+// Bodies over MAX_BODY_BYTES are turned away by the extractor, in the usual envelope
+#[rstest]
+#[tokio::test]
+async fn test_body_too_large() {
+    let mut config = Config::default();
+    config.auth.admin_api_key = Some("router-admin-key".to_string());
+    config.limits.max_body_bytes = 64;
+    let body =
+        serde_json::json!({ "username": "a".repeat(100), "password": "hunter22", "role": "cook" });
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri("/admin/staff/add")
+        .header("x-api-key", "router-admin-key")
+        .header(CONTENT_TYPE, "application/json")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = test_router(&config).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json_resp: GenericResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json_resp.status_code, 413);
+    assert_eq!(json_resp.msg, "Request body is larger than 64 bytes");
+}
+
+// Every route the router serves is documented and every documented path is served
+#[rstest]
+#[tokio::test]
+async fn test_openapi_matches_routes() {
+    // Feature gated routes included
+    let config = Config::default();
+    assert!(config.features.docs && config.features.metrics);
+    assert!(config.features.websockets && config.features.sse);
+
+    let request = Request::builder()
+        .uri("/openapi.json")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::empty())
+        .unwrap();
+    let response = test_router(&config).oneshot(request).await.unwrap();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    let mut documented = BTreeSet::new();
+    for (path, operations) in spec["paths"].as_object().unwrap() {
+        for method in operations.as_object().unwrap().keys() {
+            documented.insert((method.to_uppercase(), path.clone()));
+        }
+    }
+
+    // axum's `:param` segments are written the OpenAPI way as `{param}`
+    let registered: BTreeSet<(String, String)> = route_table(&config)
+        .into_iter()
+        .map(|(method, path)| {
+            let path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(param) => format!("{{{}}}", param),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            (method.to_string(), path)
+        })
+        .collect();
+    let undocumented: Vec<_> = registered.difference(&documented).collect();
+    let unregistered: Vec<_> = documented.difference(&registered).collect();
+    assert!(
+        undocumented.is_empty() && unregistered.is_empty(),
+        "Routes missing from the spec: {:?}\nSpec paths with no route: {:?}",
+        undocumented,
+        unregistered
+    );
+}
+
+// Helpers
+// Nothing listens on the port once the listener is dropped, so connections are refused
+fn unreachable_addr() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+fn test_router(config: &Config) -> Router {
+    build_router(test_app_state(config), config)
+}
+
+fn test_app_state(config: &Config) -> AppState {
+    let mut database_config = config.database.clone();
+    // Only parsed, connect_lazy never opens a connection until a query runs
+    if database_config.url.is_empty() {
+        database_config.url = "mysql://root@localhost:3306/restaurant".to_string();
+    }
+    let app_database = database_connect(&database_config).unwrap();
+
+    // The global recorder can only be installed once per process, a handle is all the router needs
+    let metrics_handle = PrometheusBuilder::new().build_recorder().handle();
+    AppState::new(config, app_database, metrics_handle)
+}
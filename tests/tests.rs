@@ -1,110 +1,218 @@
-use dotenv::dotenv;
-use reqwest::blocking::Client;
+use hmac::{Hmac, Mac};
 use rstest::rstest;
+use sha2::Sha256;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::sync::mpsc;
 
-#[path = "../src/models/response.rs"]
-mod response;
-use response::{GenericResponse, GetSeatsResponse, ItemsResponse};
+use restaurant_api_client::models::database::{Role, Scope, Table};
+use restaurant_api_client::models::request::{
+    AddItemsRequest, BulkFormat, CreateApiKeyRequest, CreateStaffRequest, CreateWebhookRequest,
+    GetItemRequest, ImportMode, ItemMatch, ItemSort, LoginRequest, LogoutRequest, RefreshRequest,
+    ReportGroup, ReportQuery, SearchItemsRequest, SortDirection, TableItem,
+};
+use restaurant_api_client::models::response::{
+    DomainEvent, EventMessage, GenericResponse, ImportReport, StreamMessage,
+};
+use restaurant_api_client::{Error, EventSocket, EventStream, Method, StreamEvent};
 
-#[path = "../src/models/request.rs"]
-mod request;
-use request::{AddItemsRequest, GetItemRequest, TableItem};
+mod harness;
+use harness::TestApp;
 
-#[path = "../src/models/database.rs"]
-mod database;
+#[rstest]
+#[tokio::test]
+async fn test_health() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+
+    match client.liveness().await {
+        Ok(json_resp) => {
+            assert!(json_resp.status_code == 200);
+            println!("\n=> Route: /health/live\n=> Response: {:?}\n", json_resp);
+        }
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            panic!("Failed to get health check response");
+        }
+    };
+}
 
 #[rstest]
-fn test_health() {
-    let (client, host) = get_test_server();
-    let route = &"/health".to_string();
+#[case("http_requests_total")] // Request counts from earlier requests
+#[case("http_request_duration_seconds_bucket")] // Latency histogram
+#[case("db_pool_connections{state=\"idle\"}")] // Pool usage
+#[case("db_pool_acquire_seconds_bucket")] // Time requests waited for a connection
+#[case("restaurant_open_items{table_id=\"1\"}")] // Sample table 1 has items
+#[case("restaurant_kitchen_queue_length")] // Kitchen queue gauge
+#[tokio::test]
+async fn test_metrics(#[case] expected_metric: &str) {
+    let app = TestApp::spawn().await;
+    seed_sample_table(&app).await;
+    let client = app.client();
+    // Make sure at least one request has been recorded, and one that took a connection
+    let _ = client.liveness().await;
+    let _ = client.get_seats(1).await;
 
-    match client.get(host + &route).send() {
-        Ok(response) => {
-            assert!(response.status() == 200);
+    match client.get_metrics().await {
+        Ok(body) => {
+            assert!(body.contains(expected_metric));
             println!(
-                "\n=> Route: {}\n=> Response: {}\n",
-                route,
-                response.text().unwrap()
+                "\n=> Route: /metrics\n=> Found {} in response\n",
+                expected_metric
             );
         }
+        Err(err) => {
+            eprintln!("\n=> Route: /metrics\n=> Unintended error: {}\n", err);
+            panic!("Failed to get metrics response");
+        }
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_readiness() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+
+    match client.readiness().await {
+        Ok(json_resp) => {
+            assert_eq!(json_resp.status, "ready");
+            assert!(json_resp
+                .components
+                .iter()
+                .all(|component| component.status == "up"));
+            println!("\n=> Route: /health/ready\n=> Response: {:?}\n", json_resp);
+        }
         Err(err) => {
             eprintln!("Error: {:?}", err);
-            panic!("Failed to get health check response");
+            panic!("Failed to get readiness check response");
         }
     };
 }
 
+// A database created from the first init.sql, before schema_migrations, keeps its rows and
+// ends up at the version the server expects
+#[tokio::test]
+async fn test_migrate_baseline_database() {
+    let app = TestApp::spawn_on_schema(include_str!("harness/baseline_init.sql")).await;
+    let client = app.client();
+
+    let json_resp = client.readiness().await.unwrap();
+    assert_eq!(json_resp.status, "ready");
+    assert_eq!(count_items(&app, 1).await, 4);
+    assert_eq!(count_items(&app, 2).await, 2);
+
+    // Tables added by the migrations are usable
+    let created = client
+        .create_api_key(&CreateApiKeyRequest {
+            name: "migrated".to_string(),
+            scopes: vec![Scope::Read],
+        })
+        .await
+        .unwrap();
+    let migrated_key = app.anonymous().with_api_key(&created.key);
+    assert_eq!(status_of(&migrated_key.get_seats(1).await), 200);
+    add_table(&app, 30, 2).await.unwrap();
+}
+
+#[rstest]
+#[case(None)] // Server generates a request id
+#[case(Some("test-request-id"))] // Client supplied request id is kept
+#[tokio::test]
+async fn test_request_id(#[case] request_id: Option<&str>) {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    let mut request = client.request(Method::GET, "/health/live");
+    if let Some(request_id) = request_id {
+        request = request.header("x-request-id", request_id);
+    }
+
+    let response = request.send().await.unwrap();
+    let echoed = response
+        .headers()
+        .get("x-request-id")
+        .expect("x-request-id header not echoed")
+        .to_str()
+        .unwrap()
+        .to_string();
+    match request_id {
+        Some(request_id) => assert_eq!(echoed, request_id),
+        None => assert!(!echoed.is_empty()),
+    }
+    println!("\n=> Route: /health/live\n=> Request id: {}\n", echoed);
+}
+
 #[rstest]
 #[case(1, 200, 4)] // Get table that exists, has 4 seats
 #[case(999, 500, 0)] // Get table that doesn't exist
-fn test_get_seats(
+#[tokio::test]
+async fn test_get_seats(
     #[case] table_id: u32,
     #[case] expected_status: u16,
     #[case] expected_seats: u32,
 ) {
-    let (client, host) = get_test_server();
-    let route = "/table/".to_string() + &table_id.to_string();
-
-    match client.get(host + &route).send() {
-        Ok(response) => {
-            assert!(response.status().as_u16() == expected_status);
+    let app = TestApp::spawn().await;
+    seed_sample_table(&app).await;
+    let result = app.client().get_seats(table_id).await;
+    assert_eq!(status_of(&result), expected_status);
 
-            if expected_status == 200 {
-                let json_resp = response.json::<GetSeatsResponse>().unwrap();
-                assert_eq!(json_resp.seats, expected_seats);
-                println!(
-                    "\n=> Route: {}\n=> Response for table {}: {:?}\n",
-                    route, table_id, json_resp
-                );
-            } else {
-                println!(
-                    "\n=> Route: {}\n=> Intended error response: {}\n",
-                    route,
-                    response.text().unwrap()
-                );
-            }
+    match result {
+        Ok(json_resp) => {
+            assert_eq!(json_resp.body.seats, expected_seats);
+            println!(
+                "\n=> Route: /table/{}\n=> Response for table {}: {:?}\n",
+                table_id, table_id, json_resp.body
+            );
         }
         Err(err) => {
-            eprintln!("\n=> Route: {}\n=> Uninteded error: {}\n", route, err);
-            panic!("Failed to get number of seats response");
+            println!(
+                "\n=> Route: /table/{}\n=> Intended error response: {}\n",
+                table_id, err
+            );
         }
     };
 }
 
 #[rstest]
-#[case(999, 1, 200)] // Add table that doesnt exist
-#[case(999, 1, 500)] // Add table that already exists
-fn test_add_table(#[case] table_id: u32, #[case] seats: u32, #[case] expected_status: u16) {
-    match add_table(table_id, seats) {
-        Ok(response) => {
-            assert!(response.status().as_u16() == expected_status);
+#[case(999, 1, false, 200)] // Add table that doesnt exist
+#[case(999, 1, true, 500)] // Add table that already exists
+#[tokio::test]
+async fn test_add_table(
+    #[case] table_id: u32,
+    #[case] seats: u32,
+    #[case] exists: bool,
+    #[case] expected_status: u16,
+) {
+    let app = TestApp::spawn().await;
+    if exists {
+        app.table(table_id).seats(seats).create().await;
+    }
+    let result = add_table(&app, table_id, seats).await;
+    assert_eq!(status_of(&result), expected_status);
 
-            if expected_status == 200 {
-                let json_resp = response.json::<response::GenericResponse>().unwrap();
-                assert_eq!(
-                    json_resp.msg,
-                    format!(
-                        "Sucessfully created new table {} with {} seats",
-                        table_id, seats
-                    )
-                );
+    match result {
+        Ok(json_resp) => {
+            assert_eq!(
+                json_resp.msg,
+                format!(
+                    "Sucessfully created new table {} with {} seats",
+                    table_id, seats
+                )
+            );
 
-                println!(
-                    "\n=> Route: /table/add\n=> Response for table {}: {:?}\n",
-                    table_id, json_resp
-                );
-            } else {
-                println!(
-                    "\n=> Route: /table/add\n=> Intended error response: {}\n",
-                    response.text().unwrap()
-                );
-                let _ = delete_table_by_id(table_id); // Cleanup
-            }
+            println!(
+                "\n=> Route: /table/add\n=> Response for table {}: {:?}\n",
+                table_id, json_resp
+            );
         }
-
         Err(err) => {
-            eprintln!("\n=> Route: /table/add\n=> Unintended error: {}\n", err);
-            panic!("Failed to get add table response");
+            println!(
+                "\n=> Route: /table/add\n=> Intended error response: {}\n",
+                err
+            );
         }
     };
 }
@@ -112,20 +220,21 @@ fn test_add_table(#[case] table_id: u32, #[case] seats: u32, #[case] expected_st
 #[rstest]
 #[case(999, 1, 200)] // Delete table that exists
 #[case(999, 0, 200)] // Delete table that doesn't exist
-fn test_delete_table_by_id(
+#[tokio::test]
+async fn test_delete_table_by_id(
     #[case] table_id: u32,
     #[case] rows_affected: u64,
     #[case] expected_status: u16,
 ) {
+    let app = TestApp::spawn().await;
     if rows_affected == 1 {
-        let _ = add_table(table_id, 1); // Add table for deletion
+        app.table(table_id).create().await; // Add table for deletion
     }
-    match delete_table_by_id(table_id) {
-        Ok(response) => {
-            assert!(response.status().as_u16() == expected_status);
-
-            let json_resp = response.json::<GenericResponse>().unwrap();
+    let result = delete_table_by_id(&app, table_id).await;
+    assert_eq!(status_of(&result), expected_status);
 
+    match result {
+        Ok(json_resp) => {
             assert_eq!(json_resp.rows, Some(rows_affected));
 
             println!(
@@ -133,7 +242,6 @@ fn test_delete_table_by_id(
                 table_id, table_id, json_resp
             );
         }
-
         Err(err) => {
             eprintln!(
                 "\n=> Route: /delete/{}\n=> Unintended error: {}\n",
@@ -145,20 +253,24 @@ fn test_delete_table_by_id(
 }
 
 #[rstest]
-#[case(GetItemRequest{table_id: 1, item: None, customer_id: None}, 4, 200)] // Get all items for table 1
-#[case(GetItemRequest{table_id: 1, item: Some("Bun Cha".to_string()), customer_id: None}, 2, 200)] // Get specific item for table 1
-#[case(GetItemRequest{table_id: 1, item: Some("Bun Cha".to_string()), customer_id: Some("Anthony Bourdain".to_string())}, 1, 200)] // Get specific item for table 1 and customer
-#[case(GetItemRequest{table_id: 999, item: None, customer_id: None}, 0, 200)] // Get items for table that doesn't exist
-fn test_get_items(
+#[case(GetItemRequest{table_id: 1, item: None, customer_id: None, ..Default::default()}, 4, 200)] // Get all items for table 1
+#[case(GetItemRequest{table_id: 1, item: Some("Bun Cha".to_string()), customer_id: None, ..Default::default()}, 2, 200)] // Get specific item for table 1
+#[case(GetItemRequest{table_id: 1, item: Some("Bun Cha".to_string()), customer_id: Some("Anthony Bourdain".to_string()), ..Default::default()}, 1, 200)] // Get specific item for table 1 and customer
+#[case(GetItemRequest{table_id: 999, item: None, customer_id: None, ..Default::default()}, 0, 200)] // Get items for table that doesn't exist
+#[case(GetItemRequest{table_id: 1, created_before: chrono::DateTime::from_timestamp(0, 0), ..Default::default()}, 0, 200)] // Nothing was created before the time range
+#[tokio::test]
+async fn test_get_items(
     #[case] request: GetItemRequest,
     #[case] expected_rows: usize,
     #[case] expected_status: u16,
 ) {
-    match get_items(request) {
-        Ok(response) => {
-            assert!(response.status().as_u16() == expected_status);
+    let app = TestApp::spawn().await;
+    seed_sample_table(&app).await;
+    let result = app.client().get_items(&request).await;
+    assert_eq!(status_of(&result), expected_status);
 
-            let json_resp = response.json::<ItemsResponse>().unwrap();
+    match result {
+        Ok(json_resp) => {
             assert_eq!(json_resp.items.len(), expected_rows);
 
             println!(
@@ -175,18 +287,20 @@ fn test_get_items(
 
 #[rstest]
 #[case(AddItemsRequest{to_add: vec![ TableItem{table_id: 999, item: "Burger".to_string(), customer_id: Some("Bob".to_string())}] } , 1, 200)]
-fn test_add_item(
+#[tokio::test]
+async fn test_add_item(
     #[case] request: AddItemsRequest,
     #[case] expected_rows: u64,
     #[case] expected_status: u16,
 ) {
-    let _ = add_table(999, 1); // Add table for item
+    let app = TestApp::spawn().await;
+    app.table(999).create().await; // Add table for item
 
-    match add_item(request) {
-        Ok(response) => {
-            assert!(response.status().as_u16() == expected_status);
+    let result = add_items(&app, request).await;
+    assert_eq!(status_of(&result), expected_status);
 
-            let json_resp = response.json::<response::GenericResponse>().unwrap();
+    match result {
+        Ok(json_resp) => {
             assert_eq!(json_resp.rows, Some(expected_rows));
 
             println!(
@@ -199,50 +313,70 @@ fn test_add_item(
             panic!("Failed to get add items response");
         }
     }
-    let _ = delete_table_by_id(999); // Cleanup table and item associated with table
+}
+
+#[rstest]
+#[case(101, 413)] // More items than MAX_ITEMS_PER_REQUEST
+#[case(100_000, 413)] // Body larger than MAX_BODY_BYTES
+#[tokio::test]
+async fn test_add_item_limits(#[case] num_items: usize, #[case] expected_status: u16) {
+    let request = AddItemsRequest {
+        to_add: (0..num_items)
+            .map(|_| TableItem {
+                table_id: 999,
+                item: "Burger".to_string(),
+                customer_id: Some("Bob".to_string()),
+            })
+            .collect(),
+    };
+
+    let app = TestApp::spawn().await;
+    match add_items(&app, request).await {
+        Ok(json_resp) => {
+            eprintln!(
+                "\n=> Route: /items/add\n=> Unintended response: {:?}\n",
+                json_resp
+            );
+            panic!("Oversized request was accepted");
+        }
+        Err(err) => {
+            assert_eq!(err.status(), Some(expected_status));
+            println!(
+                "\n=> Route: /items/add\n=> Intended error response: {}\n",
+                err
+            );
+        }
+    }
 }
 
 #[rstest]
 #[case(999, 1, 200)] // Delete item that exists
 #[case(999, 0, 200)] // Delete item that doesn't exist
-fn test_delete_item_by_id(
+#[tokio::test]
+async fn test_delete_item_by_id(
     #[case] table_id: u32,
     #[case] rows_affected: u64,
     #[case] expected_status: u16,
 ) {
+    let app = TestApp::spawn().await;
+    let client = app.client();
     let item_id = if rows_affected == 1 {
-        // Add table for item
-        let _ = add_table(table_id, 1);
-
-        // Add an item for deletion
-        let _ = add_item(AddItemsRequest {
-            to_add: vec![TableItem {
-                table_id: table_id,
-                item: "Burger".to_string(),
-                customer_id: Some("Bob".to_string()),
-            }],
-        });
-
-        // Fetch the id of the item that was just added
-        get_items(GetItemRequest {
-            table_id: table_id,
-            item: Some("Burger".to_string()),
-            customer_id: Some("Bob".to_string()),
-        })
-        .unwrap()
-        .json::<ItemsResponse>()
-        .unwrap()
-        .items[0]
+        // Add table and an item for deletion
+        app.table(table_id).create().await;
+        app.item(table_id, "Burger")
+            .customer("Bob")
+            .create()
+            .await
             .id
     } else {
         999
     };
 
-    match delete_item_by_id(item_id) {
-        Ok(response) => {
-            assert!(response.status().as_u16() == expected_status);
+    let result = client.delete_item_by_id(item_id, None).await;
+    assert_eq!(status_of(&result), expected_status);
 
-            let json_resp = response.json::<GenericResponse>().unwrap();
+    match result {
+        Ok(json_resp) => {
             assert_eq!(json_resp.rows, Some(rows_affected));
 
             println!(
@@ -250,7 +384,6 @@ fn test_delete_item_by_id(
                 item_id, item_id, json_resp
             );
         }
-
         Err(err) => {
             eprintln!(
                 "\n=> Route: /items/delete/{}\n=> Unintended error: {}\n",
@@ -272,31 +405,27 @@ fn test_delete_item_by_id(
     item: "Burger".to_string(),
     customer_id: Some("Bob".to_string()),
 }, 0, 200)] // Delete item that doesn't exist
-fn test_delete_item(
+#[tokio::test]
+async fn test_delete_item(
     #[case] item: TableItem,
     #[case] expected_rows: u64,
     #[case] expected_status: u16,
 ) {
+    let app = TestApp::spawn().await;
     if expected_rows > 0 {
-        // Add table for item
-        let _ = add_table(item.table_id, 1);
-
-        // Add an item for deletion
-        let _ = add_item(AddItemsRequest {
-            to_add: vec![TableItem {
-                table_id: item.table_id,
-                item: "Burger".to_string(),
-                customer_id: Some("Bob".to_string()),
-            }],
-        });
+        // Add table and an item for deletion
+        app.table(item.table_id).create().await;
+        app.item(item.table_id, "Burger")
+            .customer("Bob")
+            .create()
+            .await;
     };
-    let table_id = item.table_id; // Needed for cleanup
 
-    match delete_item(item) {
-        Ok(response) => {
-            assert!(response.status().as_u16() == expected_status);
+    let result = app.client().delete_item(&item).await;
+    assert_eq!(status_of(&result), expected_status);
 
-            let json_resp = response.json::<GenericResponse>().unwrap();
+    match result {
+        Ok(json_resp) => {
             assert_eq!(json_resp.rows, Some(expected_rows));
 
             println!(
@@ -304,72 +433,1106 @@ fn test_delete_item(
                 json_resp
             );
         }
-
         Err(err) => {
             eprintln!("\n=> Route: /items/delete\n=> Unintended error: {}\n", err);
             panic!("Failed to get delete item response");
         }
     }
-    let _ = delete_table_by_id(table_id); // Cleanup table
 }
 
-// Helpers
-type TestResponse = Result<reqwest::blocking::Response, reqwest::Error>;
+#[rstest]
+#[case(997, true)] // Undo item delete then table delete
+#[case(996, false)] // Nothing to undo for table that was never deleted
+#[tokio::test]
+async fn test_undo_table(#[case] table_id: u32, #[case] has_deletes: bool) {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    if !has_deletes {
+        match client.undo_table(table_id).await {
+            Ok(json_resp) => {
+                eprintln!(
+                    "\n=> Route: /table/undo/{}\n=> Unintended response: {:?}\n",
+                    table_id, json_resp
+                );
+                panic!("Undid a delete that never happened");
+            }
+            Err(err) => {
+                assert_eq!(err.status(), Some(404));
+                println!(
+                    "\n=> Route: /table/undo/{}\n=> Intended error response: {}\n",
+                    table_id, err
+                );
+            }
+        }
+        return;
+    }
+
+    app.table(table_id).create().await;
+    app.item(table_id, "Burger").customer("Bob").create().await;
+    let _ = client
+        .delete_item(&TableItem {
+            table_id,
+            item: "Burger".to_string(),
+            customer_id: Some("Bob".to_string()),
+        })
+        .await;
+    let _ = delete_table_by_id(&app, table_id).await;
 
-fn get_test_server() -> (Client, String) {
-    // Need env vars for connecting to host
-    dotenv().ok();
-    let app_host = std::env::var("APP_HOST").expect("APP_HOST env var not set!");
-    let app_port = std::env::var("APP_PORT").expect("APP_PORT env var not set!");
-    let addr = format!("http://{}:{}", app_host, app_port);
-    println!("\n=> Host: {}\n", addr,);
-    (Client::new(), addr)
+    // Latest delete is undone first: the table comes back without the item
+    let json_resp = client.undo_table(table_id).await.unwrap();
+    assert_eq!(json_resp.status_code, 200);
+    assert_eq!(json_resp.rows, Some(1));
+    assert_eq!(count_items(&app, table_id).await, 0);
+
+    // Then the deleted item
+    let json_resp = client.undo_table(table_id).await.unwrap();
+    assert_eq!(json_resp.status_code, 200);
+    assert_eq!(json_resp.rows, Some(1));
+    assert_eq!(count_items(&app, table_id).await, 1);
+
+    println!(
+        "\n=> Route: /table/undo/{}\n=> Response for table {}: {:?}\n",
+        table_id, table_id, json_resp
+    );
 }
 
-fn add_table(table_id: u32, seats: u32) -> TestResponse {
-    let (client, host) = get_test_server();
-    let route = "/table/add".to_string();
+#[tokio::test]
+async fn test_undo_failed_restore_keeps_window() {
+    let app = TestApp::spawn_with(|config| config.undo.window_secs = 2).await;
+    let client = app.client();
+    let table_id = 979;
+    app.table(table_id).create().await;
+    let _ = delete_table_by_id(&app, table_id).await;
 
+    // The id is taken again, so the restore fails and the entry is put back
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    app.table(table_id).create().await;
+    let result = client.undo_table(table_id).await;
+    assert_eq!(status_of(&result), 500);
+
+    // Past the window of the original delete the entry is gone, the failed restore didn't
+    // extend it
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    let result = client.undo_table(table_id).await;
+    assert_eq!(status_of(&result), 404);
+}
+
+#[rstest]
+#[case(vec![Scope::Read], 200, 403)] // Read key can read but not manage tables
+#[case(vec![Scope::Read, Scope::ManageTables], 200, 200)] // Key with both scopes
+#[tokio::test]
+async fn test_api_key_scopes(
+    #[case] scopes: Vec<Scope>,
+    #[case] expected_read_status: u16,
+    #[case] expected_write_status: u16,
+) {
+    let app = TestApp::spawn().await;
+    seed_sample_table(&app).await;
+    let client = app.client();
+    let created = client
+        .create_api_key(&CreateApiKeyRequest {
+            name: "test key".to_string(),
+            scopes: scopes.clone(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(created.scopes, scopes);
+
+    let listed = client.get_api_keys().await.unwrap();
+    assert!(listed.keys.iter().any(|key| key.id as u64 == created.id));
+
+    // No key at all
+    let anonymous = app.anonymous();
+    assert_eq!(status_of(&anonymous.get_seats(1).await), 401);
+
+    let key_client = app.anonymous().with_api_key(&created.key);
+    assert_eq!(
+        status_of(&key_client.get_seats(1).await),
+        expected_read_status
+    );
+    assert_eq!(
+        status_of(&key_client.delete_table_by_id(995, None).await),
+        expected_write_status
+    );
+
+    // Revoked keys are rejected
+    let json_resp = client.revoke_api_key(created.id as u32).await.unwrap();
+    assert_eq!(json_resp.rows, Some(1));
+    assert_eq!(status_of(&key_client.get_seats(1).await), 401);
+
+    println!(
+        "\n=> Route: /admin/keys\n=> Response for revoke key {}: {:?}\n",
+        created.id, json_resp
+    );
+}
+
+#[rstest]
+#[case(Role::Cook, 403)] // Cooks can't delete tables
+#[case(Role::Server, 200)] // Servers can
+#[tokio::test]
+async fn test_staff_session(#[case] role: Role, #[case] expected_delete_status: u16) {
+    let app = TestApp::spawn().await;
+    seed_sample_table(&app).await;
+    let client = app.client();
+    let username = format!("test_{}", role.as_str());
+    let password = "hunter22".to_string();
+    let json_resp = client
+        .create_staff(&CreateStaffRequest {
+            username: username.clone(),
+            password: password.clone(),
+            role,
+        })
+        .await
+        .unwrap();
+    assert_eq!(json_resp.rows, Some(1));
+
+    // Wrong password, and an unknown username gets the same answer
+    let anonymous = app.anonymous();
+    let wrong_password = anonymous
+        .login(&LoginRequest {
+            username: username.clone(),
+            password: "wrong".to_string(),
+        })
+        .await;
+    assert_eq!(status_of(&wrong_password), 401);
+    let unknown_user = anonymous
+        .login(&LoginRequest {
+            username: format!("{}_unknown", username),
+            password: "wrong".to_string(),
+        })
+        .await;
+    assert_eq!(status_of(&unknown_user), 401);
+    assert_eq!(
+        wrong_password.unwrap_err().to_string(),
+        unknown_user.unwrap_err().to_string()
+    );
+
+    let tokens = anonymous
+        .login(&LoginRequest {
+            username: username.clone(),
+            password,
+        })
+        .await
+        .unwrap();
+
+    let staff_client = app.anonymous().with_bearer_token(&tokens.access_token);
+    assert_eq!(status_of(&staff_client.get_seats(1).await), 200);
+    assert_eq!(
+        status_of(&staff_client.delete_table_by_id(995, None).await),
+        expected_delete_status
+    );
+
+    // Refresh tokens are single use
+    let refreshed = anonymous
+        .refresh(&RefreshRequest {
+            refresh_token: tokens.refresh_token.clone(),
+        })
+        .await
+        .unwrap();
+    let result = anonymous
+        .refresh(&RefreshRequest {
+            refresh_token: tokens.refresh_token,
+        })
+        .await;
+    assert_eq!(status_of(&result), 401);
+
+    // Logging out revokes both tokens
+    let staff_client = app.anonymous().with_bearer_token(&refreshed.access_token);
+    let json_resp = staff_client
+        .logout(&LogoutRequest {
+            refresh_token: Some(refreshed.refresh_token.clone()),
+        })
+        .await
+        .unwrap();
+    assert_eq!(json_resp.rows, Some(2));
+    assert_eq!(status_of(&staff_client.get_seats(1).await), 401);
+    let result = anonymous
+        .refresh(&RefreshRequest {
+            refresh_token: refreshed.refresh_token,
+        })
+        .await;
+    assert_eq!(status_of(&result), 401);
+
+    println!(
+        "\n=> Route: /auth/logout\n=> Response for {}: {:?}\n",
+        username, json_resp
+    );
+}
+
+#[rstest]
+#[case(Some(vec![995]), false)] // Subscribed to one table, other tables are filtered out
+#[case(None, true)] // Whole kitchen gets every table
+#[tokio::test]
+async fn test_events_ws(#[case] tables: Option<Vec<u32>>, #[case] expect_other_tables: bool) {
+    let app = TestApp::spawn().await;
+    let mut socket = app
+        .client()
+        .events_ws(tables.as_deref())
+        .await
+        .expect("Failed to open websocket");
+    match next_ws_event(&mut socket).await {
+        StreamEvent::Control(StreamMessage::Subscribed { tables: subscribed }) => {
+            assert_eq!(subscribed.is_some(), tables.is_some())
+        }
+        other => panic!("Expected subscription ack, got {:?}", other),
+    }
+
+    let _ = add_table(&app, 994, 1).await;
+    let _ = add_table(&app, 995, 1).await;
+    let _ = add_items(
+        &app,
+        AddItemsRequest {
+            to_add: vec![TableItem {
+                table_id: 995,
+                item: "Pho".to_string(),
+                customer_id: None,
+            }],
+        },
+    )
+    .await;
+    let _ = delete_table_by_id(&app, 995).await;
+
+    let mut events = Vec::new();
+    loop {
+        let message = match next_ws_event(&mut socket).await {
+            StreamEvent::Event(message) => message,
+            other => panic!("Expected an event, got {:?}", other),
+        };
+        let done = matches!(message.event, DomainEvent::TableDeleted { table_id: 995 });
+        events.push(message);
+        if done {
+            break;
+        }
+    }
+
+    // Ids only ever increase
+    assert!(events.windows(2).all(|pair| pair[0].id < pair[1].id));
+    let table_events: Vec<&str> = events
+        .iter()
+        .filter(|message| message.event.table_id() == 995)
+        .map(|message| match message.event {
+            DomainEvent::ItemAdded { .. } => "item_added",
+            DomainEvent::ItemDeleted { .. } => "item_deleted",
+            DomainEvent::TableAdded { .. } => "table_added",
+            DomainEvent::TableDeleted { .. } => "table_deleted",
+        })
+        .collect();
+    assert_eq!(
+        table_events,
+        vec!["table_added", "item_added", "item_deleted", "table_deleted"]
+    );
+    assert_eq!(
+        events.iter().any(|message| message.event.table_id() == 994),
+        expect_other_tables
+    );
+
+    println!(
+        "\n=> Route: /events/ws\n=> Events for tables {:?}: {:?}\n",
+        tables, events
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_events_sse() {
+    let app = TestApp::spawn().await;
+    let mut stream = open_events_sse(&app, 993, None).await;
+
+    let _ = add_table(&app, 993, 1).await;
+    let (added_id, added) = next_sse_event(&mut stream).await;
+    assert!(matches!(added.event, DomainEvent::TableAdded { .. }));
+    let _ = delete_table_by_id(&app, 993).await;
+    let (deleted_id, deleted) = next_sse_event(&mut stream).await;
+    assert!(matches!(
+        deleted.event,
+        DomainEvent::TableDeleted { table_id: 993 }
+    ));
+    drop(stream);
+
+    // Reconnecting with the id of the add replays the delete that came after it
+    let mut stream = open_events_sse(&app, 993, Some(added_id)).await;
+    let (replayed_id, replayed) = next_sse_event(&mut stream).await;
+    assert_eq!(replayed_id, deleted_id);
+    assert!(matches!(
+        replayed.event,
+        DomainEvent::TableDeleted { table_id: 993 }
+    ));
+
+    println!(
+        "\n=> Route: /events/sse\n=> Replayed after {}: {:?}\n",
+        added_id, replayed
+    );
+}
+
+#[rstest]
+#[case(u64::MAX)] // Largest id a header can carry
+#[case(5)] // Id from before a restart, the new run hasn't got that far
+#[tokio::test]
+async fn test_events_sse_unknown_last_event_id(#[case] last_event_id: u64) {
+    let app = TestApp::spawn().await;
+    let mut stream = open_events_sse(&app, 992, Some(last_event_id)).await;
+
+    // The client can't tell what it missed, so it is told to refetch before the live events
+    let event = tokio::time::timeout(Duration::from_secs(5), stream.next_event())
+        .await
+        .expect("Timed out waiting for an event")
+        .expect("Event stream failed")
+        .expect("Event stream closed");
+    assert!(matches!(
+        event,
+        StreamEvent::Control(StreamMessage::Lagged { missed: 1 })
+    ));
+    let _ = add_table(&app, 992, 1).await;
+    let (_, added) = next_sse_event(&mut stream).await;
+    assert!(matches!(
+        added.event,
+        DomainEvent::TableAdded { ref table } if table.id == 992
+    ));
+}
+
+#[rstest]
+#[case(200, false)] // Receiver accepts the first delivery
+#[case(500, true)] // Receiver keeps failing, the delivery ends up dead
+#[tokio::test]
+async fn test_webhooks(#[case] receiver_status: u16, #[case] expect_dead: bool) {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    let (url, mut deliveries) = start_webhook_receiver(receiver_status);
+    let webhook = client
+        .create_webhook(&CreateWebhookRequest {
+            url,
+            events: Some(vec!["table_added".to_string()]),
+            secret: Some("test-secret".to_string()),
+        })
+        .await
+        .unwrap();
+
+    let _ = add_table(&app, 992, 1).await;
+
+    // Every attempt is signed with the subscription secret over "{timestamp}.{body}"
+    let (headers, body) = tokio::time::timeout(Duration::from_secs(10), deliveries.recv())
+        .await
+        .ok()
+        .flatten()
+        .expect("No webhook delivery received");
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"test-secret").unwrap();
+    mac.update(format!("{}.{}", headers["x-webhook-timestamp"], body).as_bytes());
+    let expected_signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    assert_eq!(
+        headers["x-webhook-signature"],
+        format!("sha256={}", expected_signature)
+    );
+    let message: EventMessage = serde_json::from_str(&body).unwrap();
+    assert!(matches!(
+        message.event,
+        DomainEvent::TableAdded { ref table } if table.id == 992
+    ));
+
+    if expect_dead {
+        let started = Instant::now();
+        let dead = loop {
+            let dead_letters = client.get_dead_letters().await.unwrap();
+            if let Some(delivery) = dead_letters
+                .deliveries
+                .into_iter()
+                .find(|delivery| delivery.subscription_id as u64 == webhook.id)
+            {
+                break delivery;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(30),
+                "Delivery never reached the dead letter list"
+            );
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        };
+        assert_eq!(dead.attempts, app.config.webhooks.max_attempts);
+        assert_eq!(dead.last_status_code, Some(500));
+        println!(
+            "\n=> Route: /admin/webhooks/dead\n=> Dead delivery: {:?}\n",
+            dead
+        );
+    }
+}
+
+// Deliveries are written with the change, so an event stream that falls behind, here on an
+// import of more events than the bus holds, loses nothing
+#[tokio::test]
+async fn test_webhooks_outlast_event_bus() {
+    let app = TestApp::spawn_with(|config| config.events.channel_capacity = 2).await;
+    let client = app.client();
+    let (url, mut deliveries) = start_webhook_receiver(200);
     client
-        .put(host + &route)
-        .json(&database::Table {
-            id: table_id,
-            seats: seats,
+        .create_webhook(&CreateWebhookRequest {
+            url,
+            events: Some(vec!["item_added".to_string()]),
+            secret: None,
         })
+        .await
+        .unwrap();
+    app.table(978).create().await;
+
+    let items: String = (0..20)
+        .map(|n| format!("{{\"table_id\":978,\"item\":\"Pho {}\"}}\n", n))
+        .collect();
+    client
+        .import_items(&items, BulkFormat::Ndjson, ImportMode::AllOrNothing)
+        .await
+        .unwrap();
+
+    let mut delivered = BTreeSet::new();
+    while delivered.len() < 20 {
+        let (headers, body) = tokio::time::timeout(Duration::from_secs(10), deliveries.recv())
+            .await
+            .ok()
+            .flatten()
+            .expect("Not every webhook delivery was received");
+        let message: EventMessage = serde_json::from_str(&body).unwrap();
+        // The delivery id doubles as the event id
+        assert_eq!(headers["x-webhook-id"], message.id.to_string());
+        match message.event {
+            DomainEvent::ItemAdded { item } => delivered.insert(item.item),
+            event => panic!("Unexpected event {:?}", event),
+        };
+    }
+}
+
+#[rstest]
+#[case("Pho", 200, 1)] // Retry with the same body is replayed, the item is only added once
+#[case("Bun Cha", 422, 1)] // Same key with a different body is rejected
+#[tokio::test]
+async fn test_idempotency_key(
+    #[case] retry_item: &str,
+    #[case] expected_retry_status: u16,
+    #[case] expected_items: usize,
+) {
+    let app = TestApp::spawn().await;
+    app.table(991).create().await;
+    let idempotency_key = "test-idempotency-key";
+    let request = |item: &str| AddItemsRequest {
+        to_add: vec![TableItem {
+            table_id: 991,
+            item: item.to_string(),
+            customer_id: None,
+        }],
+    };
+
+    let client = app.client();
+    let first = client
+        .with_idempotency_key(idempotency_key)
+        .add_items(&request("Pho"))
+        .await
+        .unwrap();
+
+    // Sent raw to see whether the response was replayed
+    let retry = client
+        .request(Method::PUT, "/items/add")
+        .header("idempotency-key", idempotency_key)
+        .json(&request(retry_item))
         .send()
+        .await
+        .unwrap();
+    assert_eq!(retry.status().as_u16(), expected_retry_status);
+    if expected_retry_status == 200 {
+        assert_eq!(retry.headers()["idempotent-replayed"], "true");
+        let replayed = retry.json::<GenericResponse>().await.unwrap();
+        assert_eq!(
+            (replayed.msg, replayed.status_code, replayed.rows),
+            (first.msg, first.status_code, first.rows)
+        );
+    } else {
+        println!(
+            "\n=> Route: /items/add\n=> Intended error response: {}\n",
+            retry.text().await.unwrap()
+        );
+    }
+    assert_eq!(count_items(&app, 991).await, expected_items);
 }
 
-fn delete_table_by_id(table_id: u32) -> TestResponse {
-    let (client, host) = get_test_server();
-    let route = "/table/delete/".to_string() + &table_id.to_string();
+// Replays carry the headers of the first response, here the current ETag of a 412
+#[rstest]
+#[tokio::test]
+async fn test_idempotency_key_replays_etag() {
+    let app = TestApp::spawn().await;
+    let table_id = 977;
+    app.table(table_id).create().await;
+    let client = app.client();
+    let current = client.get_seats(table_id).await.unwrap().etag.unwrap();
+
+    let mut etags = Vec::new();
+    for replayed in [false, true] {
+        let response = client
+            .request(Method::DELETE, &format!("/table/delete/{}", table_id))
+            .header("idempotency-key", "test-idempotency-key-etag")
+            .header("if-match", "\"table-977-v0\"")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 412);
+        assert_eq!(
+            response.headers().contains_key("idempotent-replayed"),
+            replayed
+        );
+        etags.push(response.headers()["etag"].to_str().unwrap().to_string());
+    }
+    assert_eq!(etags, vec![current.clone(), current]);
+}
+
+#[rstest]
+#[case("all_or_nothing", 200)] // Same query is replayed
+#[case("best_effort", 422)] // Same key and body with a different query is rejected
+#[tokio::test]
+async fn test_idempotency_key_query(#[case] retry_mode: &str, #[case] expected_retry_status: u16) {
+    let app = TestApp::spawn().await;
+    app.table(980).create().await;
+    let idempotency_key = "test-idempotency-key-query";
+    let body = "{\"table_id\":980,\"item\":\"Pho\"}\n";
+    let import = |mode: &str| {
+        app.client()
+            .request(
+                Method::PUT,
+                &format!("/admin/import/items?format=ndjson&mode={}", mode),
+            )
+            .header("idempotency-key", idempotency_key)
+            .header("content-type", "application/x-ndjson")
+            .body(body)
+            .send()
+    };
 
-    client.delete(host + &route).send()
+    let first = import("all_or_nothing").await.unwrap();
+    assert_eq!(first.status().as_u16(), 200);
+    let retry = import(retry_mode).await.unwrap();
+    assert_eq!(retry.status().as_u16(), expected_retry_status);
+    if expected_retry_status != 200 {
+        println!(
+            "\n=> Route: /admin/import/items\n=> Intended error response: {}\n",
+            retry.text().await.unwrap()
+        );
+    }
+    assert_eq!(count_items(&app, 980).await, 1);
 }
 
-fn get_items(request: GetItemRequest) -> TestResponse {
-    let (client, host) = get_test_server();
-    let route = "/items".to_string();
+#[rstest]
+#[case(false, 412)] // Stale ETag, the table is kept
+#[case(true, 200)] // Current ETag deletes the table, undo moves the version on
+#[tokio::test]
+async fn test_table_etag(#[case] current: bool, #[case] expected_status: u16) {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    let table_id = 990;
+    app.table(table_id).create().await;
 
-    client.post(host + &route).json(&request).send()
+    let etag = client.get_seats(table_id).await.unwrap().etag.unwrap();
+    assert_eq!(etag, "\"table-990-v1\"");
+
+    let if_match = if current {
+        etag.clone()
+    } else {
+        "\"table-990-v0\"".to_string()
+    };
+    let result = client.delete_table_by_id(table_id, Some(&if_match)).await;
+    assert_eq!(status_of(&result), expected_status);
+    match result {
+        // The current ETag comes back so the client can retry
+        Err(Error::Api {
+            etag: current_etag,
+            msg,
+            ..
+        }) => {
+            assert_eq!(current_etag, Some(etag));
+            println!(
+                "\n=> Route: /table/delete/{}\n=> Intended error response: {}\n",
+                table_id, msg
+            );
+        }
+        _ => {
+            let _ = client.undo_table(table_id).await;
+            let restored = client.get_seats(table_id).await.unwrap();
+            assert_ne!(restored.etag, Some(etag.clone()));
+            // The ETag read before the delete no longer matches the restored table
+            let result = client.delete_table_by_id(table_id, Some(&etag)).await;
+            assert_eq!(status_of(&result), 412);
+        }
+    }
 }
 
-fn add_item(request: AddItemsRequest) -> TestResponse {
-    let (client, host) = get_test_server();
-    let route = "/items/add".to_string();
+// A table created again under the same id must not pick up the ETag of the one deleted before
+#[tokio::test]
+async fn test_table_etag_after_recreate() {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    let table_id = 991;
+    add_table(&app, table_id, 4).await.unwrap();
+    let stale = client.get_seats(table_id).await.unwrap().etag.unwrap();
+
+    delete_table_by_id(&app, table_id).await.unwrap();
+    add_table(&app, table_id, 4).await.unwrap();
+    let current = client.get_seats(table_id).await.unwrap().etag.unwrap();
+    assert_ne!(current, stale);
 
-    client.put(host + &route).json(&request).send()
+    let result = client.delete_table_by_id(table_id, Some(&stale)).await;
+    assert_eq!(status_of(&result), 412);
+    assert!(client.get_seats(table_id).await.is_ok());
 }
 
-fn delete_item_by_id(item_id: u32) -> TestResponse {
-    let (client, host) = get_test_server();
-    let route = "/items/delete/".to_string() + &item_id.to_string();
+#[rstest]
+#[case(false, 412, 1)] // Stale ETag, the item is kept
+#[case(true, 200, 0)] // Current ETag deletes the item
+#[tokio::test]
+async fn test_item_etag(
+    #[case] current: bool,
+    #[case] expected_status: u16,
+    #[case] expected_items: usize,
+) {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    let table_id = 989;
+    app.table(table_id).create().await;
+    let item = app.item(table_id, "Pho").create().await;
+    assert_eq!(item.version, 1);
+
+    let etag = client.get_item(item.id).await.unwrap().etag.unwrap();
+    assert_eq!(etag, format!("\"item-{}-v1\"", item.id));
 
-    client.delete(host + &route).send()
+    let if_match = if current {
+        etag
+    } else {
+        format!("\"item-{}-v2\"", item.id)
+    };
+    let result = client.delete_item_by_id(item.id, Some(&if_match)).await;
+    assert_eq!(status_of(&result), expected_status);
+    assert_eq!(count_items(&app, table_id).await, expected_items);
 }
 
-fn delete_item(item: TableItem) -> TestResponse {
-    let (client, host) = get_test_server();
-    let route = "/items/delete".to_string();
+#[rstest]
+#[case(ItemSort::Item, SortDirection::Asc, vec!["Banh Mi", "Bun Cha", "Cha Gio", "Pho", "Xoi"])] // Alphabetical
+#[case(ItemSort::CreatedAt, SortDirection::Desc, vec!["Cha Gio", "Xoi", "Banh Mi", "Pho", "Bun Cha"])] // Latest first, same timestamps fall back to id
+#[tokio::test]
+async fn test_get_items_pages(
+    #[case] sort: ItemSort,
+    #[case] direction: SortDirection,
+    #[case] expected_items: Vec<&str>,
+) {
+    let app = TestApp::spawn().await;
+    let client = app.client();
+    let table_id = 988;
+    app.table(table_id).create().await;
+    // Added in one request so they share a timestamp
+    let _ = add_items(
+        &app,
+        AddItemsRequest {
+            to_add: ["Bun Cha", "Pho", "Banh Mi", "Xoi", "Cha Gio"]
+                .iter()
+                .map(|item| TableItem {
+                    table_id,
+                    item: item.to_string(),
+                    customer_id: None,
+                })
+                .collect(),
+        },
+    )
+    .await;
 
-    client.delete(host + &route).json(&item).send()
+    // Pages of 2 until the server stops handing out a cursor
+    let mut items = Vec::new();
+    let mut pages = 0;
+    let mut cursor = None;
+    loop {
+        let page = client
+            .get_items(&GetItemRequest {
+                table_id,
+                sort,
+                direction,
+                limit: Some(2),
+                cursor,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        pages += 1;
+        items.extend(page.items.into_iter().map(|item| item.item));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(items, expected_items);
+    assert_eq!(pages, 3);
+}
+
+#[rstest]
+#[case(Some(0), None)] // Page size has to be at least 1
+#[case(None, Some("not-a-cursor"))] // Cursors are only accepted as handed out
+#[tokio::test]
+async fn test_get_items_invalid_page(#[case] limit: Option<usize>, #[case] cursor: Option<&str>) {
+    let app = TestApp::spawn().await;
+    let result = app
+        .client()
+        .get_items(&GetItemRequest {
+            table_id: 1,
+            limit,
+            cursor: cursor.map(|cursor| cursor.to_string()),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(status_of(&result), 400);
+    println!(
+        "\n=> Route: /items\n=> Intended error response: {}\n",
+        result.unwrap_err()
+    );
+}
+
+#[rstest]
+#[case(Some("pho"), ItemMatch::Exact, false, None, vec![987])] // Exact names match case-insensitively
+#[case(Some("PHO"), ItemMatch::Prefix, false, None, vec![986, 987])] // Prefix match across tables
+#[case(Some("pho"), ItemMatch::Prefix, true, None, vec![986])] // Case-sensitive prefix
+#[case(None, ItemMatch::Exact, false, Some("Search Bob"), vec![986])] // Where is a customer sitting
+#[tokio::test]
+async fn test_search_items(
+    #[case] item: Option<&str>,
+    #[case] item_match: ItemMatch,
+    #[case] case_sensitive: bool,
+    #[case] customer_id: Option<&str>,
+    #[case] expected_tables: Vec<u32>,
+) {
+    let app = TestApp::spawn().await;
+    app.table(987).create().await;
+    app.table(986).create().await;
+    app.item(987, "Pho").create().await;
+    app.item(986, "pho ga")
+        .customer("Search Bob")
+        .create()
+        .await;
+    let result = app
+        .client()
+        .search_items(&SearchItemsRequest {
+            item: item.map(|item| item.to_string()),
+            item_match,
+            case_sensitive,
+            customer_id: customer_id.map(|customer_id| customer_id.to_string()),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(status_of(&result), 200);
+
+    let mut tables: Vec<u32> = result
+        .unwrap()
+        .items
+        .iter()
+        .map(|item| item.table_id)
+        .collect();
+    tables.sort();
+    assert_eq!(tables, expected_tables);
+}
+
+#[rstest]
+#[case(ImportMode::AllOrNothing, 422, 0)] // One bad row keeps every row out
+#[case(ImportMode::BestEffort, 200, 2)] // Valid rows go in, the bad ones are reported
+#[tokio::test]
+async fn test_import_items(
+    #[case] mode: ImportMode,
+    #[case] expected_status: u16,
+    #[case] expected_imported: usize,
+) {
+    let app = TestApp::spawn().await;
+    app.table(985).create().await;
+    let csv = "table_id,item,cook_time,customer_id\n\
+        985,Pho,10,Import Bob\n\
+        984,Pho,10,\n\
+        985,Xoi,soon,\n\
+        985,\"Banh Mi, extra pate\",,\"Bob, Jr\"\n";
+    let result = app.client().import_items(csv, BulkFormat::Csv, mode).await;
+    assert_eq!(status_of(&result), expected_status);
+
+    let report: ImportReport = match result {
+        Ok(report) => report,
+        Err(Error::Api { msg, .. }) => serde_json::from_str(&msg).unwrap(),
+        Err(err) => panic!("Import failed: {}", err),
+    };
+    assert_eq!(report.imported, expected_imported as u64);
+    assert_eq!(report.failed, 2);
+    let lines: Vec<usize> = report.errors.iter().map(|err| err.line).collect();
+    assert_eq!(lines, vec![3, 4]);
+    assert_eq!(count_items(&app, 985).await, expected_imported);
+}
+
+#[rstest]
+#[case(ImportMode::AllOrNothing)]
+#[case(ImportMode::BestEffort)]
+#[tokio::test]
+async fn test_import_publishes_events(#[case] mode: ImportMode) {
+    let app = TestApp::spawn().await;
+    let mut stream = open_events_sse(&app, 981, None).await;
+
+    let tables = "{\"id\":981,\"seats\":2}\n";
+    let items = "{\"table_id\":981,\"item\":\"Pho\",\"customer_id\":\"Import Bob\"}\n";
+    let client = app.client();
+    client
+        .import_tables(tables, BulkFormat::Ndjson, mode)
+        .await
+        .unwrap();
+    client
+        .import_items(items, BulkFormat::Ndjson, mode)
+        .await
+        .unwrap();
+
+    let (_, added) = next_sse_event(&mut stream).await;
+    assert!(matches!(
+        added.event,
+        DomainEvent::TableAdded { ref table } if table.id == 981 && table.seats == 2
+    ));
+    let (_, added) = next_sse_event(&mut stream).await;
+    assert!(matches!(
+        added.event,
+        DomainEvent::ItemAdded { ref item } if item.table_id == 981 && item.item == "Pho"
+    ));
+}
+
+#[rstest]
+#[case(BulkFormat::Csv)]
+#[case(BulkFormat::Ndjson)]
+#[tokio::test]
+async fn test_export_import_round_trip(#[case] format: BulkFormat) {
+    let source = TestApp::spawn().await;
+    source.table(983).seats(6).create().await;
+    source
+        .item(983, "Bun Cha")
+        .customer("Doe, Jane")
+        .create()
+        .await;
+    source.item(983, "Ca Phe \"Sua\" Da").create().await;
+    let tables = source.client().export_tables(format).await.unwrap();
+    let items = source.client().export_items(format).await.unwrap();
+
+    // Both databases are new, so the items get the same ids again
+    let target = TestApp::spawn().await;
+    let report = target
+        .client()
+        .import_tables(&tables, format, ImportMode::AllOrNothing)
+        .await
+        .unwrap();
+    assert_eq!((report.imported, report.failed), (1, 0));
+    let report = target
+        .client()
+        .import_items(&items, format, ImportMode::AllOrNothing)
+        .await
+        .unwrap();
+    assert_eq!((report.imported, report.failed), (2, 0));
+    assert_eq!(target.client().export_tables(format).await.unwrap(), tables);
+    assert_eq!(target.client().export_items(format).await.unwrap(), items);
+}
+
+#[rstest]
+#[case(ReportGroup::Dish, vec![(Some("Pho"), 2, 12.0), (Some("Bun Cha"), 1, 5.0)])] // Best sellers first
+#[case(ReportGroup::Hour, vec![(Some("11"), 2, 12.0), (Some("19"), 1, 5.0)])] // Hours of the day in order
+#[case(ReportGroup::Table, vec![(Some("982"), 3, 9.67)])] // Average is rounded
+#[case(ReportGroup::Customer, vec![(Some("Report Bob"), 2, 12.0), (None, 1, 5.0)])] // Walk-ins are grouped under null
+#[tokio::test]
+async fn test_report(
+    #[case] group_by: ReportGroup,
+    #[case] expected_rows: Vec<(Option<&str>, u64, f64)>,
+) {
+    let app = TestApp::spawn().await;
+    let at = |time: &str| format!("2024-03-01T{}Z", time).parse().unwrap();
+    app.table(982).create().await;
+    for (item, cook_time, customer_id, created_at) in [
+        ("Pho", 10, Some("Report Bob"), at("11:05:00")),
+        ("Pho", 14, Some("Report Bob"), at("11:40:00")),
+        ("Bun Cha", 5, None, at("19:10:00")),
+        // The next day is outside the report
+        ("Pho", 10, None, at("09:00:00") + chrono::Duration::days(1)),
+    ] {
+        let mut fixture = app
+            .item(982, item)
+            .cook_time(cook_time)
+            .created_at(created_at);
+        if let Some(customer_id) = customer_id {
+            fixture = fixture.customer(customer_id);
+        }
+        fixture.create().await;
+    }
+
+    let query = ReportQuery {
+        from: Some(at("00:00:00")),
+        ..Default::default()
+    };
+    let report = app.client().get_report(group_by, &query).await.unwrap();
+    let rows: Vec<(Option<&str>, u64, f64)> = report
+        .rows
+        .iter()
+        .map(|row| (row.key.as_deref(), row.items, row.avg_cook_time))
+        .collect();
+    assert_eq!(rows, expected_rows);
+    assert_eq!(report.total_items, 3);
+    assert!(report.rows.iter().all(|row| row.revenue.is_none()));
+
+    let csv = app.client().get_report_csv(group_by, &query).await.unwrap();
+    assert_eq!(csv.lines().count(), expected_rows.len() + 1);
+
+    // Served items are deleted and still counted, an undone delete doesn't count them twice
+    delete_table_by_id(&app, 982).await.unwrap();
+    let served = app.client().get_report(group_by, &query).await.unwrap();
+    assert_eq!(served.rows, report.rows);
+    app.client().undo_table(982).await.unwrap();
+    let restored = app.client().get_report(group_by, &query).await.unwrap();
+    assert_eq!(restored.rows, report.rows);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_report_invalid_range() {
+    let app = TestApp::spawn().await;
+    let from = chrono::DateTime::from_timestamp(1_709_251_200, 0);
+    let result = app
+        .client()
+        .get_report(
+            ReportGroup::Dish,
+            &ReportQuery {
+                from,
+                to: from,
+                ..Default::default()
+            },
+        )
+        .await;
+    assert_eq!(status_of(&result), 400);
+}
+
+// Helpers
+type TestResult<T> = Result<T, Error>;
+// Headers and body of a request received by the webhook receiver
+type ReceivedWebhook = (HashMap<String, String>, String);
+
+// Table 1 of the demo data from `restaurant-admin seed`, for tests that read existing items
+async fn seed_sample_table(app: &TestApp) {
+    app.table(1).seats(4).create().await;
+    for (item, cook_time, customer_id) in [
+        ("Bun Cha", 10, "Barack Obama"),
+        ("Hanoi Beer", 5, "Barack Obama"),
+        ("Bun Cha", 10, "Anthony Bourdain"),
+        ("Tiger Beer", 5, "Anthony Bourdain"),
+    ] {
+        app.item(1, item)
+            .cook_time(cook_time)
+            .customer(customer_id)
+            .create()
+            .await;
+    }
+}
+
+// Status of a typed call. Every route answers 200 on success.
+fn status_of<T>(result: &TestResult<T>) -> u16 {
+    match result {
+        Ok(_) => 200,
+        Err(err) => err
+            .status()
+            .unwrap_or_else(|| panic!("No response: {}", err)),
+    }
+}
+
+async fn add_table(app: &TestApp, table_id: u32, seats: u32) -> TestResult<GenericResponse> {
+    app.client()
+        .add_table(&Table {
+            id: table_id,
+            seats,
+            version: 1,
+        })
+        .await
+}
+
+async fn delete_table_by_id(app: &TestApp, table_id: u32) -> TestResult<GenericResponse> {
+    app.client().delete_table_by_id(table_id, None).await
+}
+
+async fn add_items(app: &TestApp, request: AddItemsRequest) -> TestResult<GenericResponse> {
+    app.client().add_items(&request).await
+}
+
+async fn count_items(app: &TestApp, table_id: u32) -> usize {
+    app.client()
+        .get_items(&GetItemRequest {
+            table_id,
+            item: None,
+            customer_id: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .items
+        .len()
+}
+
+// Fail the test instead of hanging when an expected event never arrives
+async fn next_ws_event(socket: &mut EventSocket) -> StreamEvent {
+    tokio::time::timeout(Duration::from_secs(5), socket.next_event())
+        .await
+        .expect("Timed out waiting for a websocket message")
+        .expect("Websocket failed")
+        .expect("Websocket closed")
+}
+
+async fn open_events_sse(app: &TestApp, table_id: u32, last_event_id: Option<u64>) -> EventStream {
+    app.client()
+        .events_sse(Some(&[table_id]), last_event_id)
+        .await
+        .expect("Failed to open event stream")
+}
+
+// Reads the next event that carries an id, skipping control messages
+async fn next_sse_event(stream: &mut EventStream) -> (u64, EventMessage) {
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), stream.next_event())
+            .await
+            .expect("Timed out waiting for an event")
+            .expect("Event stream failed")
+            .expect("Event stream closed");
+        if let StreamEvent::Event(message) = event {
+            return (message.id, message);
+        }
+    }
+}
+
+// Minimal HTTP receiver that answers every request with `status` and passes the headers and body on.
+// It runs on a thread of its own, the channel is async so waiting on it doesn't block the test's
+// runtime, which also serves the API.
+fn start_webhook_receiver(status: u16) -> (String, mpsc::UnboundedReceiver<ReceivedWebhook>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = HashMap::new();
+            let mut line = String::new();
+            let _ = reader.read_line(&mut line); // Request line
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                }
+            }
+            let length = headers
+                .get("content-length")
+                .and_then(|length| length.parse::<usize>().ok())
+                .unwrap_or(0);
+            let mut body = vec![0u8; length];
+            let _ = reader.read_exact(&mut body);
+
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {} Test\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            let _ = sender.send((headers, String::from_utf8_lossy(&body).to_string()));
+        }
+    });
+    (url, receiver)
 }